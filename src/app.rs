@@ -6,27 +6,26 @@ use winit::{
     application::ApplicationHandler,
     dpi::LogicalSize,
     event::*,
-    event_loop::{ActiveEventLoop, EventLoop},
+    event_loop::ActiveEventLoop,
     keyboard::PhysicalKey,
     window::{Window, WindowId},
 };
 
-use crate::state::State;
+use crate::{config::Config, state::State};
 
 pub struct App {
-    proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     state: Option<State>, // State as option 'cause window can't be created before the Resumed state
+    config: Config,
     last_render_time: Instant,
     frame_count: u32,
     accum_time: Duration,
 }
 
 impl App {
-    pub fn new(event_loop: &EventLoop<State>) -> Self {
-        let proxy = Some(event_loop.create_proxy());
+    pub fn new(config: Config) -> Self {
         Self {
-            proxy,
             state: None,
+            config,
             last_render_time: Instant::now(),
             frame_count: 0,
             accum_time: Duration::ZERO,
@@ -64,12 +63,22 @@ impl ApplicationHandler<State> for App {
         let window = Arc::new(
             event_loop
                 .create_window(
-                    Window::default_attributes().with_inner_size(LogicalSize::new(1280.0, 720.0)),
+                    Window::default_attributes().with_inner_size(LogicalSize::new(
+                        self.config.resolution.x,
+                        self.config.resolution.y,
+                    )),
                 )
                 .unwrap(),
         );
 
-        let mut state = pollster::block_on(State::new(window)).unwrap();
+        let mut state = match pollster::block_on(State::new(window, &self.config)) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("{e:?}");
+                event_loop.exit();
+                return;
+            }
+        };
         state.display.set_cursor_locked(true);
         self.state = Some(state);
     }
@@ -84,7 +93,6 @@ impl ApplicationHandler<State> for App {
             WindowEvent::CloseRequested => {
                 self.state.take(); // Destroy the state
                 event_loop.exit();
-                return;
             }
             WindowEvent::Resized(size) => {
                 if let Some(state) = &mut self.state {
@@ -95,6 +103,13 @@ impl ApplicationHandler<State> for App {
                 let (dt, title) = self.update_fps();
                 if let Some(state) = &mut self.state {
                     if let Some(t) = title {
+                        let mut t = format!("{t} | Scale: {:.0}%", state.render_scale() * 100.0);
+                        if let Some((generated, requested)) = state.loading_progress() {
+                            t += &format!(
+                                " | Loading world: {}% ({generated}/{requested} chunks)",
+                                generated * 100 / requested
+                            );
+                        }
                         state.display.window.set_title(&t);
                     }
                     state.update(dt);
@@ -107,6 +122,12 @@ impl ApplicationHandler<State> for App {
                         Err(wgpu::SurfaceError::OutOfMemory) => event_loop.exit(),
                         Err(e) => eprintln!("{:?}", e),
                     }
+
+                    if let Some(report) = state.benchmark_report() {
+                        println!("{report}");
+                        self.state.take();
+                        event_loop.exit();
+                    }
                 }
             }
             WindowEvent::KeyboardInput {
@@ -119,7 +140,12 @@ impl ApplicationHandler<State> for App {
                 ..
             } => {
                 if let Some(game_state) = &mut self.state {
-                    game_state.player_controller.process_keyboard(code, state);
+                    game_state.process_keyboard(code, state);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some(game_state) = &mut self.state {
+                    game_state.process_mouse_button(button, state);
                 }
             }
             _ => (),
@@ -132,10 +158,10 @@ impl ApplicationHandler<State> for App {
         _device_id: DeviceId,
         event: DeviceEvent,
     ) {
-        if let DeviceEvent::MouseMotion { delta } = event {
-            if let Some(state) = &mut self.state {
-                state.camera_controller.process_mouse(delta.0, delta.1);
-            }
+        if let DeviceEvent::MouseMotion { delta } = event
+            && let Some(state) = &mut self.state
+        {
+            state.camera_controller.process_mouse(delta.0, delta.1);
         }
     }
 
@@ -0,0 +1,101 @@
+use std::{fmt, time::Duration};
+
+use crate::camera_path::{CameraPath, CameraPose};
+
+const PLAYBACK_STEP: f32 = 1.0 / 60.0; // Path time advanced per frame, independent from the frame rate
+const WARMUP_FRAMES: usize = 10; // Frames rendered at the first pose and left out of the report
+
+// Deterministic flythrough: frame N always shows the same pose, whatever the frame rate
+pub struct Benchmark {
+    path: CameraPath,
+    frame: usize,
+    frame_times: Vec<Duration>,
+}
+
+pub struct BenchmarkReport {
+    pub frames: usize,
+    pub min: Duration,
+    pub avg: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Benchmark {
+    pub fn new(path: CameraPath) -> Self {
+        let frames = (path.duration() / PLAYBACK_STEP).ceil() as usize + 1;
+
+        Self {
+            path,
+            frame: 0,
+            frame_times: Vec::with_capacity(frames),
+        }
+    }
+
+    // Pose of the next frame, None once the whole path has been played
+    pub fn next_pose(&mut self) -> Option<CameraPose> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let pose = self.path.sample(self.playback_time());
+        self.frame += 1;
+        Some(pose)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.playback_time() > self.path.duration() + f32::EPSILON
+    }
+
+    // Frame time of the last frame given by `next_pose`
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame > WARMUP_FRAMES {
+            self.frame_times.push(frame_time);
+        }
+    }
+
+    pub fn report(&self) -> BenchmarkReport {
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_unstable();
+
+        if sorted.is_empty() {
+            return BenchmarkReport {
+                frames: 0,
+                min: Duration::ZERO,
+                avg: Duration::ZERO,
+                p99: Duration::ZERO,
+                max: Duration::ZERO,
+            };
+        }
+
+        let total: Duration = sorted.iter().sum();
+        let p99_idx = ((sorted.len() as f32 * 0.99).ceil() as usize).clamp(1, sorted.len()) - 1;
+
+        BenchmarkReport {
+            frames: sorted.len(),
+            min: sorted[0],
+            avg: total / sorted.len() as u32,
+            p99: sorted[p99_idx],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    fn playback_time(&self) -> f32 {
+        self.frame.saturating_sub(WARMUP_FRAMES) as f32 * PLAYBACK_STEP
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        write!(
+            f,
+            "Benchmark: {} frames | min {:.2}ms | avg {:.2}ms | p99 {:.2}ms | max {:.2}ms",
+            self.frames,
+            ms(self.min),
+            ms(self.avg),
+            ms(self.p99),
+            ms(self.max)
+        )
+    }
+}
@@ -0,0 +1,123 @@
+use crate::chunk::CHUNK_SIZE;
+
+const BRICK_SIZE: u32 = 8;
+const OCCUPANCY_WORDS: usize = (BRICK_SIZE * BRICK_SIZE * BRICK_SIZE / 32) as usize;
+
+// Chunk split in 2x2x2 dense bricks, as u32 words:
+// - the word offset of each brick (index x | y << 1 | z << 2), 0 if it is empty
+// - for each non empty brick, 1 occupancy bit per voxel (index x + y * 8 + z * 64), then the colors
+//   of its voxels in index order
+pub struct Brickmap {
+    words: Vec<u32>,
+}
+
+impl Brickmap {
+    pub fn new(voxels: impl Iterator<Item = (glam::IVec3, u32)>) -> Self {
+        let mut bricks: [Vec<(u32, u32)>; 8] = Default::default();
+        for (local, color) in voxels {
+            let (brick, voxel) = (local.as_uvec3() / BRICK_SIZE, local.as_uvec3() % BRICK_SIZE);
+            bricks[(brick.x | (brick.y << 1) | (brick.z << 2)) as usize].push((
+                voxel.x + voxel.y * BRICK_SIZE + voxel.z * BRICK_SIZE * BRICK_SIZE,
+                color,
+            ));
+        }
+        if bricks.iter().all(Vec::is_empty) {
+            return Self { words: Vec::new() };
+        }
+
+        let mut words = vec![0; bricks.len()];
+        for (i, voxels) in bricks.iter_mut().enumerate() {
+            if voxels.is_empty() {
+                continue;
+            }
+            voxels.sort_unstable_by_key(|(index, _)| *index);
+
+            let mut occupancy = [0_u32; OCCUPANCY_WORDS];
+            for (index, _) in voxels.iter() {
+                occupancy[*index as usize / 32] |= 1 << (index % 32);
+            }
+
+            words[i] = words.len() as u32;
+            words.extend(occupancy);
+            words.extend(voxels.iter().map(|(_, color)| color));
+        }
+
+        Self { words }
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    // Chunk box at level 0 & bricks at level 1, there is nothing in between the bricks & the voxels
+    pub fn level_boxes(&self, level: u32) -> Vec<(glam::IVec3, i32)> {
+        match level {
+            _ if self.words.is_empty() => Vec::new(),
+            0 => vec![(glam::IVec3::ZERO, CHUNK_SIZE as i32)],
+            1 => (0..8)
+                .filter(|&i| self.words[i as usize] != 0)
+                .map(|i| {
+                    let brick = glam::ivec3(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+                    (brick * BRICK_SIZE as i32, BRICK_SIZE as i32)
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_of_the_bricks() {
+        let voxels = [
+            (glam::ivec3(15, 15, 15), 4),
+            (glam::ivec3(3, 1, 0), 3),
+            (glam::ivec3(9, 0, 0), 2),
+            (glam::ivec3(0, 0, 0), 1),
+        ];
+        let brickmap = Brickmap::new(voxels.into_iter());
+        let words = brickmap.words();
+
+        // Offsets: after the 8 offsets, then after 16 occupancy words & the colors of each brick
+        assert_eq!(words[..8], [8, 26, 0, 0, 0, 0, 0, 43]);
+        assert_eq!(words.len(), 43 + OCCUPANCY_WORDS + 1);
+
+        // Brick 0: voxels 0 & 3 + 1 * 8, colors in index order
+        let brick = &words[8..26];
+        assert_eq!(brick[0], 1 | 1 << 11);
+        assert!(brick[1..OCCUPANCY_WORDS].iter().all(|word| *word == 0));
+        assert_eq!(brick[OCCUPANCY_WORDS..], [1, 3]);
+
+        // Brick x = 1: voxel 1
+        assert_eq!(words[26], 1 << 1);
+        assert_eq!(words[26 + OCCUPANCY_WORDS], 2);
+
+        // Last brick: last voxel, 511 = bit 31 of word 15
+        assert_eq!(words[43 + OCCUPANCY_WORDS - 1], 1 << 31);
+        assert_eq!(words[43 + OCCUPANCY_WORDS], 4);
+
+        let bricks: Vec<_> = brickmap
+            .level_boxes(1)
+            .into_iter()
+            .map(|(min, _)| min)
+            .collect();
+        assert_eq!(
+            bricks,
+            [
+                glam::IVec3::ZERO,
+                glam::ivec3(8, 0, 0),
+                glam::IVec3::splat(8)
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_chunks_have_no_words() {
+        let brickmap = Brickmap::new(std::iter::empty());
+        assert!(brickmap.words().is_empty());
+        assert!(brickmap.level_boxes(0).is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    chunk::Chunk,
+    config::Config,
+    svo::Svo,
+    world::{MemoryStats, World, WorldParams},
+};
+
+const VIEW_DISTANCES: [u32; 3] = [16, 32, 64];
+
+// Build times & sizes, a single world octree voxel by voxel vs each chunk structure in parallel. The
+// world is the configured one (terrain, chunk structure, grid center) at each view distance, all the
+// chunk structures are built with --compare-layouts
+pub fn run(config: &Config) -> anyhow::Result<()> {
+    println!(
+        "{:>13} {:>7} {:>26} {:>12} {:>12}",
+        "view distance", "chunks", "structure", "build", "size"
+    );
+
+    for view_distance in VIEW_DISTANCES {
+        let configured = &config.world;
+        let params = WorldParams {
+            view_distance,
+            origin: configured.origin - WorldParams::centered_origin(configured.view_distance)
+                + WorldParams::centered_origin(view_distance),
+            ..configured.clone()
+        };
+        let (chunks, generation) = timed(|| World::generate_chunks(&params));
+        let row = |name: &str, time: Duration, size: Option<usize>| {
+            let size = size.map_or(String::new(), |size| format!("{} kB", size / 1024));
+            println!(
+                "{view_distance:>13} {:>7} {name:>26} {time:>12.2?} {size:>12}",
+                chunks.len()
+            );
+        };
+        row("generation", generation, None);
+        println!("{:>13} {}", "", MemoryStats::new(&chunks));
+
+        // The world octree starts at the grid corner
+        let (grid_min, _) = params.bounds();
+        let (octree, time) = timed(|| -> anyhow::Result<Svo> {
+            let mut octree = Svo::new();
+            for entry in chunks.iter() {
+                for (index, voxel) in entry.value().iter_voxels() {
+                    let local = Chunk::index_to_local_pos(index);
+                    let position = Chunk::local_to_world_pos(&local, entry.key());
+                    octree.insert(position - grid_min, voxel.color, params.max_depth())?;
+                }
+            }
+            Ok(octree)
+        });
+        let octree = octree?;
+        row("world octree", time, Some(octree.size()));
+
+        let variants = match config.compare_layouts {
+            true => params.structure_variants(),
+            false => vec![params.clone()],
+        };
+        for params in variants {
+            let (size, time) = timed(|| World::build_structures(&chunks, &params));
+            row(&params.structure_name(), time, Some(size));
+        }
+    }
+    Ok(())
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let value = f();
+    (value, start.elapsed())
+}
@@ -1,15 +1,34 @@
 use wgpu::util::DeviceExt;
 
+use crate::{voxel::Voxel, world_position::WorldPosition};
+
 #[derive(Default)]
 pub struct CameraController {
     sensitivity: f32,
     mouse_delta: glam::DVec2,
 }
 
+// Used by the projection toggle
+#[derive(Debug, Clone, Copy)]
+pub struct CameraParams {
+    pub fov: f32,         // Vertical, in degrees
+    pub ortho_scale: f32, // Half height of the orthographic view volume in blocks
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    Perspective { fov: f32, near: f32, far: f32 },
+    Orthographic { scale: f32, near: f32, far: f32 }, // Scale = half height of the view volume in blocks
+}
+
+// The matrices are relative to the chunk of the camera (the render origin), so they stay precise
+// far from the world origin
 pub struct Camera {
-    pub position: glam::Vec3,
+    pub position: WorldPosition,
 
-    pub fov: f32,
+    pub projection: Projection,
+    params: CameraParams,
+    pub medium: Option<Voxel>, // Transparent voxel the camera is in (under water), drawn as fog
 
     pub front: glam::Vec3,
     pub right: glam::Vec3,
@@ -19,6 +38,7 @@ pub struct Camera {
     yaw: f32,
     pitch: f32,
 
+    view_proj: glam::Mat4,
     inv_view_proj: glam::Mat4,
 }
 
@@ -26,8 +46,11 @@ pub struct Camera {
 #[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     pub inv_view_proj: [[f32; 4]; 4],
-    pub origin: [f32; 3],
-    _padding0: f32,
+    pub view_proj: [[f32; 4]; 4],
+    pub origin: [f32; 3], // Relative to the render origin
+    pub is_orthographic: u32,
+    pub medium: u32, // Packed color, 0 (air) outside of a transparent voxel
+    _padding: [u32; 3],
 }
 
 pub struct CameraResource {
@@ -62,17 +85,27 @@ impl CameraController {
         // Reset the mouse delta
         self.mouse_delta = glam::DVec2::ZERO;
 
-        camera.update_vectors();
-        camera.update_matrices(width, height);
+        camera.update(width, height);
+    }
+
+    // Drop the pending mouse motion (the camera is driven by something else)
+    pub fn reset(&mut self) {
+        self.mouse_delta = glam::DVec2::ZERO;
     }
 }
 
 impl Camera {
-    pub fn new(position: glam::Vec3) -> Self {
+    pub fn new(position: WorldPosition, params: CameraParams) -> Self {
         Self {
             position,
             yaw: -90.0,
-            fov: 60.0,
+            projection: Projection::Perspective {
+                fov: params.fov,
+                near: 0.1,
+                far: 1000.0,
+            },
+            params,
+            medium: None,
             world_up: glam::Vec3 {
                 x: 0.0,
                 y: 1.0,
@@ -82,6 +115,7 @@ impl Camera {
             up: glam::Vec3::ZERO,
             right: glam::Vec3::ZERO,
             pitch: 0.0,
+            view_proj: glam::Mat4::ZERO,
             inv_view_proj: glam::Mat4::ZERO,
         }
     }
@@ -97,40 +131,117 @@ impl Camera {
         self.up = self.right.cross(self.front).normalize();
     }
 
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    // Chunk whose min corner is the origin of the render space
+    pub fn render_origin(&self) -> glam::IVec3 {
+        self.position.chunk
+    }
+
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-89.0, 89.0);
+    }
+
+    pub fn update(&mut self, width: usize, height: usize) {
+        self.update_vectors();
+        self.update_matrices(width, height);
+    }
+
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective { near, far, .. } => Projection::Orthographic {
+                scale: self.params.ortho_scale,
+                near,
+                far,
+            },
+            Projection::Orthographic { near, far, .. } => Projection::Perspective {
+                fov: self.params.fov,
+                near,
+                far,
+            },
+        };
+    }
+
     fn update_matrices(&mut self, width: usize, height: usize) {
-        if self.fov <= 0.0 {
+        if !self.projection.is_valid() {
             return;
         }
 
         let aspect = width as f32 / height as f32;
 
-        let view = glam::Mat4::look_at_rh(self.position, self.position + self.front, self.up);
-        let proj = glam::Mat4::perspective_rh(self.fov.to_radians(), aspect, 0.1, 1000.0);
+        let eye = self.position.offset;
+        let view = glam::Mat4::look_at_rh(eye, eye + self.front, self.up);
+        let proj = self.projection.matrix(aspect);
 
-        let view_proj = proj * view;
+        self.view_proj = proj * view;
+        self.inv_view_proj = self.view_proj.inverse();
+    }
+}
 
-        self.inv_view_proj = view_proj.inverse();
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> glam::Mat4 {
+        match *self {
+            Projection::Perspective { fov, near, far } => {
+                glam::Mat4::perspective_rh(fov.to_radians(), aspect, near, far)
+            }
+            Projection::Orthographic { scale, near, far } => glam::Mat4::orthographic_rh(
+                -scale * aspect,
+                scale * aspect,
+                -scale,
+                scale,
+                near,
+                far,
+            ),
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        matches!(self, Projection::Orthographic { .. })
+    }
+
+    fn is_valid(&self) -> bool {
+        match *self {
+            Projection::Perspective { fov, near, far } => fov > 0.0 && near > 0.0 && far > near,
+            Projection::Orthographic { scale, near, far } => scale > 0.0 && far > near,
+        }
     }
 }
 
-impl CameraUniform {
-    pub fn new(camera: &Camera) -> Self {
+impl Default for CameraParams {
+    fn default() -> Self {
         Self {
-            inv_view_proj: camera.inv_view_proj.to_cols_array_2d(),
-            origin: camera.position.to_array(),
-            ..Default::default()
+            fov: 60.0,
+            ortho_scale: 64.0,
         }
     }
+}
+
+impl CameraUniform {
+    pub fn new(camera: &Camera) -> Self {
+        let mut uniform = Self::default();
+        uniform.update(camera);
+        uniform
+    }
 
     pub fn update(&mut self, camera: &Camera) {
         self.inv_view_proj = camera.inv_view_proj.to_cols_array_2d();
-        self.origin = camera.position.to_array();
+        self.view_proj = camera.view_proj.to_cols_array_2d();
+        self.origin = camera.position.offset.to_array();
+        self.is_orthographic = camera.projection.is_orthographic() as u32;
+        self.medium = camera.medium.map_or(0, |voxel| voxel.color);
     }
 }
 
 impl CameraResource {
     pub fn new(device: &wgpu::Device, camera: &Camera) -> Self {
-        let uniform = CameraUniform::new(&camera);
+        let uniform = CameraUniform::new(camera);
 
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -142,7 +253,7 @@ impl CameraResource {
             label: Some("Camera Layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -175,3 +286,52 @@ impl CameraResource {
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAR: f64 = 1e6;
+
+    fn camera_at(position: glam::DVec3) -> Camera {
+        let params = CameraParams {
+            fov: 60.0,
+            ortho_scale: 64.0,
+        };
+        let mut camera = Camera::new(WorldPosition::from_dvec3(position), params);
+        camera.set_orientation(30.0, -20.0);
+        camera.update(1920, 1080);
+        camera
+    }
+
+    #[test]
+    fn view_matrices_only_depend_on_the_offset_in_the_chunk() {
+        let near = camera_at(glam::dvec3(3.25, 20.5, 7.75));
+        for far in [FAR, -FAR] {
+            let camera = camera_at(glam::dvec3(far + 3.25, far + 20.5, far + 7.75));
+            assert_eq!(camera.position.offset, near.position.offset);
+            assert_eq!(camera.view_proj, near.view_proj);
+            assert_eq!(camera.inv_view_proj, near.inv_view_proj);
+        }
+    }
+
+    #[test]
+    fn view_matrices_are_precise_far_from_the_origin() {
+        for far in [FAR, -FAR] {
+            let camera = camera_at(glam::dvec3(far + 0.3, far + 0.3, far + 0.3));
+            let project = |position: WorldPosition| {
+                camera
+                    .view_proj
+                    .project_point3(position.relative_to(camera.render_origin()))
+            };
+
+            // Straight ahead lands in the middle of the screen
+            let ahead = project(camera.position + camera.front * 10.0);
+            assert!(ahead.truncate().abs().max_element() < 1e-4);
+
+            // A few cm to the right moves the projection, a world space f32 would round it away
+            let right = project(camera.position + camera.front * 10.0 + camera.right * 0.02);
+            assert!(right.x - ahead.x > 1e-3);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::Context;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::camera::Camera;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CameraPose {
+    pub time: f32,             // Seconds since the beginning of the recording
+    pub position: glam::DVec3, // f64 so the paths stay precise far from the origin
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+// Keyframes sorted by time, sampled with a Catmull-Rom spline
+pub struct CameraPath {
+    poses: Vec<CameraPose>,
+}
+
+// Write one pose per tick, as a text line: "time x y z yaw pitch"
+pub struct CameraPathRecorder {
+    writer: BufWriter<File>,
+    time: f32,
+}
+
+impl CameraPose {
+    pub fn from_camera(time: f32, camera: &Camera) -> Self {
+        Self {
+            time,
+            position: camera.position.as_dvec3(),
+            yaw: camera.yaw(),
+            pitch: camera.pitch(),
+        }
+    }
+
+    fn parse(line: &str) -> anyhow::Result<Self> {
+        let values = line
+            .split_whitespace()
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let [time, x, y, z, yaw, pitch] = values[..] else {
+            anyhow::bail!("expected 6 values, got {}", values.len());
+        };
+
+        Ok(Self {
+            time: time as f32,
+            position: glam::dvec3(x, y, z),
+            yaw: yaw as f32,
+            pitch: pitch as f32,
+        })
+    }
+}
+
+impl CameraPath {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Cannot open camera path {}", path.display()))?;
+
+        let mut poses: Vec<CameraPose> = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let pose = CameraPose::parse(line)
+                .with_context(|| format!("{}:{}: invalid pose", path.display(), i + 1))?;
+
+            // Drop duplicated timestamps, they would make a zero-length spline segment
+            if poses.last().is_some_and(|last| pose.time <= last.time) {
+                continue;
+            }
+            poses.push(pose);
+        }
+
+        anyhow::ensure!(
+            poses.len() >= 2,
+            "Camera path {} needs at least 2 poses",
+            path.display()
+        );
+
+        Ok(Self { poses })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.poses[self.poses.len() - 1].time - self.poses[0].time
+    }
+
+    // Sample the path at `time` seconds from its start, clamped to the path ends
+    pub fn sample(&self, time: f32) -> CameraPose {
+        let time = (self.poses[0].time + time).clamp(self.poses[0].time, self.end_time());
+
+        // Find the segment [p1, p2] containing the time
+        let i = self
+            .poses
+            .partition_point(|p| p.time <= time)
+            .clamp(1, self.poses.len() - 1);
+
+        let p0 = &self.poses[i.saturating_sub(2)];
+        let p1 = &self.poses[i - 1];
+        let p2 = &self.poses[i];
+        let p3 = &self.poses[(i + 1).min(self.poses.len() - 1)];
+
+        let t = (time - p1.time) / (p2.time - p1.time);
+
+        // Interpolated relative to p1, the offsets between the poses are small enough for f32
+        let relative = |pose: &CameraPose| (pose.position - p1.position).as_vec3();
+        let (r0, r2, r3) = (relative(p0), relative(p2), relative(p3));
+        let offset = glam::Vec3::from_array(std::array::from_fn(|axis| {
+            catmull_rom(r0[axis], 0.0, r2[axis], r3[axis], t)
+        }));
+
+        CameraPose {
+            time,
+            position: p1.position + offset.as_dvec3(),
+            // Yaw is never wrapped by the camera, so it can be interpolated directly
+            yaw: catmull_rom(p0.yaw, p1.yaw, p2.yaw, p3.yaw, t),
+            pitch: catmull_rom(p0.pitch, p1.pitch, p2.pitch, p3.pitch, t).clamp(-89.0, 89.0),
+        }
+    }
+
+    fn end_time(&self) -> f32 {
+        self.poses[self.poses.len() - 1].time
+    }
+}
+
+impl CameraPathRecorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Cannot create camera path {}", path.display()))?;
+
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "# time x y z yaw pitch")?;
+
+        Ok(Self { writer, time: 0.0 })
+    }
+
+    pub fn record(&mut self, dt: std::time::Duration, camera: &Camera) -> std::io::Result<()> {
+        let pose = CameraPose::from_camera(self.time, camera);
+        self.time += dt.as_secs_f32();
+
+        writeln!(
+            self.writer,
+            "{} {} {} {} {} {}",
+            pose.time, pose.position.x, pose.position.y, pose.position.z, pose.yaw, pose.pitch
+        )
+    }
+}
+
+// Uniform Catmull-Rom interpolation between p1 (t = 0) and p2 (t = 1)
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
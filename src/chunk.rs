@@ -1,49 +1,153 @@
-use noise::{NoiseFn, Perlin};
-
-use crate::voxel::Voxel;
+use crate::{
+    brickmap::Brickmap,
+    decoration,
+    palette::PalettedVoxels,
+    svo::Svo,
+    terrain::Terrain,
+    voxel::Voxel,
+    world::{ChunkStructure, SvoLayout, WorldParams},
+};
 
 pub const CHUNK_SIZE: u32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
 
 pub struct Chunk {
-    voxels: Vec<Voxel>,
+    voxels: PalettedVoxels,
+    structure: Structure, // In chunk space, rebuilt after the voxel changes
+}
+
+// Acceleration structure traced on the GPU
+enum Structure {
+    Octree(Svo),
+    Brickmap(Brickmap),
 }
 
 impl Chunk {
     pub fn new() -> Self {
-        let voxels = vec![Voxel::default(); (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize];
-        Self { voxels }
+        Self {
+            voxels: PalettedVoxels::new(CHUNK_VOLUME, Voxel::default()),
+            structure: Structure::Octree(Svo::new()),
+        }
     }
 
-    pub fn generate(&mut self, perlin: &Perlin, chunk_coord: glam::IVec3) {
+    pub fn generate(&mut self, terrain: &Terrain, chunk_coord: glam::IVec3) {
+        let origin = Self::local_to_world_pos(&glam::IVec3::ZERO, &chunk_coord);
         for lx in 0..CHUNK_SIZE {
             for lz in 0..CHUNK_SIZE {
-                let world_x = chunk_coord.x * CHUNK_SIZE as i32 + lx as i32;
-                let world_z = chunk_coord.z * CHUNK_SIZE as i32 + lz as i32;
-
-                let height = perlin.get([world_x as f64 / 64.0, world_z as f64 / 64.0]);
-                let height = ((height + 1.0) / 2.0 * CHUNK_SIZE as f64) as usize;
-
-                for ly in 0..height.min(CHUNK_SIZE as usize) {
-                    let color = if ly < 2 {
-                        glam::vec3(0.5, 0.4, 0.3)
-                    } else if ly == height - 1 {
-                        glam::vec3(0.2, 0.8, 0.3)
-                    } else {
-                        glam::vec3(0.6, 0.6, 0.6)
-                    };
-
-                    self.voxels[(lx + (ly as u32 * CHUNK_SIZE) + (lz * CHUNK_SIZE * CHUNK_SIZE))
-                        as usize] = Voxel::new(color, true);
+                let column = terrain.column(origin.x + lx as i32, origin.z + lz as i32);
+                for ly in 0..column.top() {
+                    self.set(glam::uvec3(lx, ly, lz), column.voxel(ly));
+                }
+            }
+        }
+    }
+
+    // Place the features (trees, boulders) rooted in the chunk, only into air. Return the voxels
+    // that fall in other chunks, in world space
+    pub fn decorate(
+        &mut self,
+        terrain: &Terrain,
+        chunk_coord: glam::IVec3,
+    ) -> Vec<(glam::IVec3, Voxel)> {
+        let origin = Self::local_to_world_pos(&glam::IVec3::ZERO, &chunk_coord);
+        let mut spilled = Vec::new();
+        for (position, voxel) in decoration::features(terrain, chunk_coord) {
+            let local = position - origin;
+            if local.cmplt(glam::IVec3::ZERO).any()
+                || local.cmpge(glam::IVec3::splat(CHUNK_SIZE as i32)).any()
+            {
+                spilled.push((position, voxel));
+            } else if self.get(local.as_uvec3()).is_air() {
+                self.set(local.as_uvec3(), voxel);
+            }
+        }
+        spilled
+    }
+
+    pub fn get(&self, local: glam::UVec3) -> Voxel {
+        self.voxels.get(Self::index(local))
+    }
+
+    pub fn set(&mut self, local: glam::UVec3, voxel: Voxel) {
+        self.voxels.set(Self::index(local), voxel);
+    }
+
+    // None if the voxels are not all the same
+    pub fn uniform(&self) -> Option<Voxel> {
+        self.voxels.uniform()
+    }
+
+    // Voxel storage, without the acceleration structure
+    pub fn memory_size(&self) -> usize {
+        self.voxels.memory_size()
+    }
+
+    pub fn rebuild_structure(&mut self, params: &WorldParams) {
+        let depth = CHUNK_SIZE.ilog2();
+        let voxels = self
+            .iter_voxels()
+            .map(|(index, voxel)| (Self::index_to_local_pos(index), voxel.color));
+
+        self.structure = match (params.chunk_structure, params.svo_layout) {
+            (ChunkStructure::Brickmap, _) => Structure::Brickmap(Brickmap::new(voxels)),
+            (ChunkStructure::Octree, SvoLayout::Insertion) => {
+                let mut octree = Svo::new();
+                for (local, color) in voxels {
+                    octree
+                        .insert(local, color, depth)
+                        .expect("Chunk voxels are inside the chunk octree");
                 }
+                Structure::Octree(octree)
             }
+            (ChunkStructure::Octree, SvoLayout::Morton(node_layout)) => {
+                let octree = Svo::from_morton(voxels.collect(), depth, node_layout)
+                    .expect("Chunk voxels are inside the chunk octree");
+                Structure::Octree(octree)
+            }
+        };
+    }
+
+    // Acceleration structure as uploaded in the chunk pool, empty if the chunk has no voxel
+    pub fn gpu_words(&self) -> &[u32] {
+        match &self.structure {
+            Structure::Octree(octree) if octree.is_empty() => &[],
+            Structure::Octree(octree) => bytemuck::cast_slice(&octree.nodes),
+            Structure::Brickmap(brickmap) => brickmap.words(),
+        }
+    }
+
+    // Boxes of the acceleration structure nodes at `level`, in chunk space
+    pub fn level_boxes(
+        &self,
+        level: u32,
+        visit: impl Fn(glam::IVec3, i32) -> bool,
+    ) -> Vec<(glam::IVec3, i32)> {
+        match &self.structure {
+            Structure::Octree(octree) => octree.level_boxes(level, CHUNK_SIZE.ilog2(), visit),
+            Structure::Brickmap(brickmap) => brickmap
+                .level_boxes(level)
+                .into_iter()
+                .filter(|(min, size)| visit(*min, *size))
+                .collect(),
         }
     }
 
-    pub fn iter_voxels<'a>(&'a self) -> impl Iterator<Item = (usize, &'a Voxel)> {
+    // Non-air voxels (alpha != 0)
+    pub fn iter_voxels(&self) -> impl Iterator<Item = (usize, Voxel)> + '_ {
+        // Nothing to decode in an empty chunk
+        let len = match self.uniform() {
+            Some(voxel) if voxel.is_air() => 0,
+            _ => CHUNK_VOLUME,
+        };
         self.voxels
             .iter()
+            .take(len)
             .enumerate()
-            .filter(|(_, v)| v.color & (0xFF) != 0)
+            .filter(|(_, voxel)| !voxel.is_air())
+    }
+
+    fn index(local: glam::UVec3) -> usize {
+        (local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE * CHUNK_SIZE) as usize
     }
 
     pub fn index_to_local_pos(index: usize) -> glam::IVec3 {
@@ -59,8 +163,4 @@ impl Chunk {
     pub fn local_to_world_pos(local_pos: &glam::IVec3, chunk_pos: &glam::IVec3) -> glam::IVec3 {
         (chunk_pos * CHUNK_SIZE as i32) + local_pos
     }
-
-    pub fn as_bytes(&self) -> &[u8] {
-        return bytemuck::cast_slice(&self.voxels);
-    }
 }
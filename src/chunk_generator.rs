@@ -0,0 +1,149 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use crate::{chunk::Chunk, terrain::Terrain, voxel::Voxel, world::WorldParams};
+
+// Generates chunks & their structures on the rayon pool, the closest to the focus (the player)
+// first. The finished chunks wait in a channel until the main thread takes them
+pub struct ChunkGenerator {
+    shared: Arc<Shared>,
+    sender: mpsc::Sender<GeneratedChunk>,
+    receiver: mpsc::Receiver<GeneratedChunk>,
+}
+
+pub struct GeneratedChunk {
+    pub chunk_coord: glam::IVec3,
+    pub chunk: Chunk,
+    pub spilled: Vec<(glam::IVec3, Voxel)>, // Decoration voxels in other chunks, in world space
+}
+
+struct Shared {
+    params: WorldParams,
+    terrain: Terrain,
+    jobs: Mutex<Jobs>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    pending: Vec<glam::IVec3>, // Farthest from the focus first, the last one is the next to start
+    running: HashSet<glam::IVec3>, // Removed when cancelled, the result is then dropped
+    focus: glam::IVec3,
+}
+
+impl ChunkGenerator {
+    pub fn new(params: WorldParams) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            shared: Arc::new(Shared {
+                terrain: Terrain::new(&params.terrain),
+                params,
+                jobs: Mutex::default(),
+            }),
+            sender,
+            receiver,
+        }
+    }
+
+    // Each job takes whichever pending chunk is the closest when it starts, not its own
+    pub fn request(&self, chunk_coords: impl IntoIterator<Item = glam::IVec3>) {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let queued = jobs.pending.len();
+        jobs.pending.extend(chunk_coords);
+        jobs.sort();
+
+        for _ in queued..jobs.pending.len() {
+            let shared = self.shared.clone();
+            let sender = self.sender.clone();
+            rayon::spawn(move || shared.run_job(&sender));
+        }
+    }
+
+    // Prioritize the chunks closest to `focus` & cancel the ones out of range, pending or running
+    pub fn retain(&self, focus: glam::IVec3, in_range: impl Fn(glam::IVec3) -> bool) {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        jobs.focus = focus;
+        jobs.pending.retain(|chunk_coord| in_range(*chunk_coord));
+        jobs.running.retain(|chunk_coord| in_range(*chunk_coord));
+        jobs.sort();
+    }
+
+    pub fn try_recv(&self) -> Option<GeneratedChunk> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Jobs {
+    fn sort(&mut self) {
+        let focus = self.focus;
+        self.pending
+            .sort_by_key(|chunk_coord| Reverse((*chunk_coord - focus).length_squared()));
+    }
+}
+
+impl Shared {
+    fn run_job(&self, sender: &mpsc::Sender<GeneratedChunk>) {
+        let chunk_coord = {
+            let mut jobs = self.jobs.lock().unwrap();
+            // Nothing left if the chunks were cancelled
+            let Some(chunk_coord) = jobs.pending.pop() else {
+                return;
+            };
+            jobs.running.insert(chunk_coord);
+            chunk_coord
+        };
+
+        let mut chunk = Chunk::new();
+        chunk.generate(&self.terrain, chunk_coord);
+        let spilled = chunk.decorate(&self.terrain, chunk_coord);
+        chunk.rebuild_structure(&self.params);
+
+        if self.jobs.lock().unwrap().running.remove(&chunk_coord) {
+            // The world may be gone already
+            let _ = sender.send(GeneratedChunk {
+                chunk_coord,
+                chunk,
+                spilled,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_chunks_match_chunk_generate() {
+        let params = WorldParams::default();
+        let terrain = Terrain::new(&params.terrain);
+        let generator = ChunkGenerator::new(params);
+        let chunk_coords = [glam::ivec3(0, 0, 0), glam::ivec3(-3, 0, 2)];
+        generator.request(chunk_coords);
+
+        let mut generated = Vec::new();
+        while generated.len() < chunk_coords.len() {
+            match generator.try_recv() {
+                Some(chunk) => generated.push(chunk),
+                None => std::thread::sleep(std::time::Duration::from_millis(1)),
+            }
+        }
+
+        for GeneratedChunk {
+            chunk_coord,
+            chunk,
+            spilled,
+        } in generated
+        {
+            let mut expected = Chunk::new();
+            expected.generate(&terrain, chunk_coord);
+            assert_eq!(spilled, expected.decorate(&terrain, chunk_coord));
+            assert!(
+                chunk.iter_voxels().eq(expected.iter_voxels()),
+                "{chunk_coord}"
+            );
+        }
+    }
+}
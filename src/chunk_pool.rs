@@ -0,0 +1,153 @@
+use std::{collections::HashMap, ops::Range};
+
+use wgpu::util::DeviceExt;
+
+use crate::svo::SvoNode;
+
+const NODE_POOL_SIZE: u64 = 32_000_000; // In bytes
+const EMPTY_CHUNK: u32 = u32::MAX; // Grid entry of a chunk without any voxel
+
+// Chunk structures on the GPU: each chunk owns a slice of the node buffer, found through a grid of
+// root indices, so updating a chunk only rewrites its own slice & grid cell. The structures only
+// use indices relative to their root, the slices are uploaded as is
+pub struct ChunkPool {
+    pub node_buffer: wgpu::Buffer,
+    pub grid_buffer: wgpu::Buffer,
+    grid_size: u32,           // In chunks, along each axis
+    grid_origin: glam::IVec3, // Chunk in the first cell
+    slices: HashMap<glam::IVec3, Range<u32>>,
+    free: Vec<Range<u32>>, // Sorted & merged free node ranges
+}
+
+impl ChunkPool {
+    pub fn new(device: &wgpu::Device, grid_size: u32, grid_origin: glam::IVec3) -> Self {
+        let node_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Chunk Pool Storage Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            size: NODE_POOL_SIZE,
+            mapped_at_creation: false,
+        });
+        let grid_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Grid Buffer"),
+            contents: bytemuck::cast_slice(&vec![EMPTY_CHUNK; grid_size.pow(3) as usize]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let capacity = (NODE_POOL_SIZE / size_of::<SvoNode>() as u64) as u32;
+        Self {
+            node_buffer,
+            grid_buffer,
+            grid_size,
+            grid_origin,
+            slices: HashMap::new(),
+            free: std::iter::once(0..capacity).collect(),
+        }
+    }
+
+    // Replace the structure of a chunk, empty words free its slice
+    pub fn upload_chunk(
+        &mut self,
+        queue: &wgpu::Queue,
+        chunk_coord: glam::IVec3,
+        words: &[u32],
+    ) -> anyhow::Result<()> {
+        let cell = self.grid_index(chunk_coord)?;
+        self.remove_chunk(queue, chunk_coord);
+        if words.is_empty() {
+            return Ok(());
+        }
+
+        let len = words.len().div_ceil(2) as u32; // 2 words per node
+        let offset = self.allocate(len).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Chunk pool is full ({} kB), cannot upload chunk {chunk_coord}",
+                NODE_POOL_SIZE / 1024
+            )
+        })?;
+
+        queue.write_buffer(
+            &self.node_buffer,
+            offset as u64 * size_of::<SvoNode>() as u64,
+            bytemuck::cast_slice(words),
+        );
+        queue.write_buffer(&self.grid_buffer, cell, bytemuck::bytes_of(&offset));
+        self.slices.insert(chunk_coord, offset..offset + len);
+        Ok(())
+    }
+
+    // Move the grid to start at `grid_origin`, the chunks left outside of it are removed. The ones
+    // still inside keep their slice, only the grid is written again
+    pub fn move_grid(&mut self, queue: &wgpu::Queue, grid_origin: glam::IVec3) {
+        self.grid_origin = grid_origin;
+
+        let outside: Vec<_> = self
+            .slices
+            .keys()
+            .filter(|chunk_coord| self.grid_index(**chunk_coord).is_err())
+            .copied()
+            .collect();
+        for chunk_coord in outside {
+            let slice = self.slices.remove(&chunk_coord).unwrap();
+            self.release(slice);
+        }
+
+        let mut grid = vec![EMPTY_CHUNK; self.grid_size.pow(3) as usize];
+        for (chunk_coord, slice) in &self.slices {
+            let cell = self.grid_index(*chunk_coord).unwrap() as usize / size_of::<u32>();
+            grid[cell] = slice.start;
+        }
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&grid));
+    }
+
+    pub fn remove_chunk(&mut self, queue: &wgpu::Queue, chunk_coord: glam::IVec3) {
+        let Some(slice) = self.slices.remove(&chunk_coord) else {
+            return;
+        };
+        if let Ok(cell) = self.grid_index(chunk_coord) {
+            queue.write_buffer(&self.grid_buffer, cell, bytemuck::bytes_of(&EMPTY_CHUNK));
+        }
+        self.release(slice);
+    }
+
+    // Byte offset of the chunk in the grid buffer
+    fn grid_index(&self, chunk_coord: glam::IVec3) -> anyhow::Result<u64> {
+        let size = self.grid_size as i32;
+        let offset = chunk_coord - self.grid_origin;
+        anyhow::ensure!(
+            offset.min_element() >= 0 && offset.max_element() < size,
+            "Chunk {chunk_coord} is outside of the {size}³ chunk grid at {}",
+            self.grid_origin
+        );
+
+        let c = offset.as_uvec3();
+        let index = c.x + c.y * self.grid_size + c.z * self.grid_size * self.grid_size;
+        Ok(index as u64 * size_of::<u32>() as u64)
+    }
+
+    // First fit
+    fn allocate(&mut self, len: u32) -> Option<u32> {
+        let i = self
+            .free
+            .iter()
+            .position(|range| range.len() >= len as usize)?;
+        let offset = self.free[i].start;
+        self.free[i].start += len;
+        if self.free[i].is_empty() {
+            self.free.remove(i);
+        }
+        Some(offset)
+    }
+
+    fn release(&mut self, slice: Range<u32>) {
+        let i = self.free.partition_point(|range| range.start < slice.start);
+        self.free.insert(i, slice);
+
+        // Merge with the next then the previous range
+        if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+            self.free[i].end = self.free.remove(i + 1).end;
+        }
+        if i > 0 && self.free[i - 1].end == self.free[i].start {
+            self.free[i - 1].end = self.free.remove(i).end;
+        }
+    }
+}
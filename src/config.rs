@@ -0,0 +1,290 @@
+use anyhow::Context;
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    camera::CameraParams,
+    chunk::CHUNK_SIZE,
+    raymarch::RaymarchMode,
+    renderer::RenderParams,
+    svo::NodeLayout,
+    upscale::UpscaleFilter,
+    world::{ChunkStructure, SvoLayout, WorldParams},
+};
+
+const USAGE: &str = "Usage: mainR_engine [options]
+  --record <file>         Record the camera path to <file>
+  --play <file>           Play a recorded camera path and print a benchmark report
+  --headless              Run --play without a window
+  --compare-layouts       With --headless, play the path once per chunk structure & octree layout.
+                          With --build-benchmark, build all of them
+  --build-benchmark       Print the generation & structure build times of the configured world
+                          for view distances 16, 32 & 64 and exit
+  --resolution <WxH>      Window / headless render resolution (default 1280x720)
+  --fov <degrees>         Vertical field of view of the perspective projection (default 60)
+  --ortho-scale <blocks>  Half height of the orthographic view, toggled with P (default 64)
+  --screenshot-scale <n>  Resolution multiplier of the F3 screenshots (default 4)
+  --render-scale <f>      Raymarch resolution scale, in ]0, 1] (default 1)
+  --min-render-scale <f>  Lower bound of the dynamic resolution (default 0.5)
+  --frame-budget <ms>     Enable the dynamic resolution, targeting this frame time
+  --upscale <filter>      Upscale filter: bilinear (default) or nearest
+  --raymarch <mode>       SVO traversal path: fragment (default) or compute
+  --chunk-structure <s>   Chunk acceleration structure: octree (default) or brickmap
+  --svo-layout <layout>   Chunk octree node order: insertion (default), bfs or dfs (Morton builds)
+  --world-origin <x,y,z>  Chunk at the min corner of the chunk grid (default -8,0,-8, centered)
+  --seed <n>              Terrain generation seed (default 1)
+  --terrain-frequency <f> Multiplier of the terrain height noise frequency (default 1)
+  --terrain-amplitude <f> Multiplier of the terrain height variations (default 1)
+  --terrain-octaves <n>   Height noise octaves, each adding finer detail (default 1)
+  --sea-level <n>         Height the terrain is generated around, below 16 (default 4)
+  --mesh <file.obj>       Mesh spawned with F7 (default: a cube)
+  --vox <file.vox>        Voxel models spawned with F8
+  --import-vox <file.vox> Place the models of <file.vox> in the world, side by side along X
+  --voxelize <file.obj>   Voxelize the mesh & place it in the world, sampling its colors or textures
+  --voxelize-resolution <n>  Voxels along the longest side of the mesh (default 64)
+  --import-origin <x,y,z> Corner of the imported models & meshes (default 0,16,0)
+  --export-vox <file.vox> Save --export-region of the world to <file.vox> and exit
+  --export-region <x0,y0,z0,x1,y1,z1>  Exported box, min included & max excluded";
+
+// Startup options, from the command line
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub record_path: Option<PathBuf>,
+    pub play_path: Option<PathBuf>,
+    pub headless: bool,
+    pub compare_layouts: bool,
+    pub build_benchmark: bool,
+    pub resolution: glam::UVec2,
+    pub screenshot_scale: u32,
+    pub mesh_path: Option<PathBuf>,
+    pub vox_path: Option<PathBuf>,
+    pub import_vox: Option<PathBuf>,
+    pub voxelize: Option<PathBuf>,
+    pub voxelize_resolution: u32,
+    pub import_origin: glam::IVec3,
+    pub export_vox: Option<PathBuf>,
+    pub export_region: Option<(glam::IVec3, glam::IVec3)>,
+    pub render: RenderParams,
+    pub camera: CameraParams,
+    pub world: WorldParams,
+}
+
+impl Config {
+    pub fn from_args() -> anyhow::Result<Self> {
+        Self::parse(std::env::args().skip(1)).context(USAGE)
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut config = Config::default();
+        let mut frame_budget_ms: Option<f32> = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Missing value for {arg}"))
+            };
+
+            match arg.as_str() {
+                "--record" => config.record_path = Some(value()?.into()),
+                "--play" => config.play_path = Some(value()?.into()),
+                "--headless" => config.headless = true,
+                "--compare-layouts" => config.compare_layouts = true,
+                "--build-benchmark" => config.build_benchmark = true,
+                "--resolution" => config.resolution = parse_resolution(&value()?)?,
+                "--fov" => config.camera.fov = value()?.parse()?,
+                "--ortho-scale" => config.camera.ortho_scale = value()?.parse()?,
+                "--screenshot-scale" => config.screenshot_scale = value()?.parse()?,
+                "--render-scale" => config.render.scale = value()?.parse()?,
+                "--min-render-scale" => config.render.min_scale = value()?.parse()?,
+                "--frame-budget" => frame_budget_ms = Some(value()?.parse()?),
+                "--upscale" => config.render.upscale_filter = parse_upscale_filter(&value()?)?,
+                "--raymarch" => config.render.raymarch_mode = parse_raymarch_mode(&value()?)?,
+                "--chunk-structure" => {
+                    config.world.chunk_structure = parse_chunk_structure(&value()?)?
+                }
+                "--svo-layout" => config.world.svo_layout = parse_svo_layout(&value()?)?,
+                "--world-origin" => config.world.origin = parse_ivec3(&value()?)?,
+                "--seed" => config.world.terrain.seed = value()?.parse()?,
+                "--terrain-frequency" => config.world.terrain.frequency = value()?.parse()?,
+                "--terrain-amplitude" => config.world.terrain.amplitude = value()?.parse()?,
+                "--terrain-octaves" => config.world.terrain.octaves = value()?.parse()?,
+                "--sea-level" => config.world.terrain.sea_level = value()?.parse()?,
+                "--mesh" => config.mesh_path = Some(value()?.into()),
+                "--vox" => config.vox_path = Some(value()?.into()),
+                "--import-vox" => config.import_vox = Some(value()?.into()),
+                "--voxelize" => config.voxelize = Some(value()?.into()),
+                "--voxelize-resolution" => config.voxelize_resolution = value()?.parse()?,
+                "--import-origin" => config.import_origin = parse_ivec3(&value()?)?,
+                "--export-vox" => config.export_vox = Some(value()?.into()),
+                "--export-region" => config.export_region = Some(parse_region(&value()?)?),
+                "--help" | "-h" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => anyhow::bail!("Unknown argument {arg}"),
+            }
+        }
+
+        anyhow::ensure!(
+            !config.headless || config.play_path.is_some(),
+            "--headless requires --play <file>"
+        );
+        anyhow::ensure!(
+            !config.compare_layouts || config.headless || config.build_benchmark,
+            "--compare-layouts requires --headless or --build-benchmark"
+        );
+        anyhow::ensure!(
+            config.screenshot_scale > 0,
+            "--screenshot-scale must be at least 1"
+        );
+        anyhow::ensure!(
+            config.camera.fov > 0.0 && config.camera.fov < 180.0,
+            "--fov must be in ]0, 180["
+        );
+        anyhow::ensure!(
+            config.camera.ortho_scale > 0.0 && config.camera.ortho_scale.is_finite(),
+            "--ortho-scale must be positive"
+        );
+        anyhow::ensure!(
+            config.render.scale > 0.0 && config.render.scale <= 1.0,
+            "--render-scale must be in ]0, 1]"
+        );
+        if let Some(ms) = frame_budget_ms {
+            anyhow::ensure!(
+                ms.is_finite() && ms > 0.0,
+                "--frame-budget must be a positive number of milliseconds"
+            );
+            let budget =
+                Duration::try_from_secs_f32(ms / 1000.0).context("--frame-budget is too large")?;
+            config.render.frame_budget = Some(budget);
+        }
+        anyhow::ensure!(
+            config.render.frame_budget.is_none()
+                || (config.render.min_scale > 0.0
+                    && config.render.min_scale <= config.render.scale),
+            "--min-render-scale must be in ]0, --render-scale]"
+        );
+        anyhow::ensure!(
+            config.record_path.is_none() || config.play_path.is_none(),
+            "--record and --play are exclusive"
+        );
+        anyhow::ensure!(
+            config.voxelize_resolution > 0,
+            "--voxelize-resolution must be at least 1"
+        );
+        let terrain = &config.world.terrain;
+        anyhow::ensure!(
+            terrain.frequency > 0.0 && terrain.frequency.is_finite(),
+            "--terrain-frequency must be positive and finite"
+        );
+        anyhow::ensure!(
+            terrain.amplitude >= 0.0 && terrain.amplitude.is_finite(),
+            "--terrain-amplitude must be finite and not negative"
+        );
+        anyhow::ensure!(terrain.octaves > 0, "--terrain-octaves must be at least 1");
+        anyhow::ensure!(
+            terrain.sea_level < CHUNK_SIZE,
+            "--sea-level must be below {CHUNK_SIZE}"
+        );
+        anyhow::ensure!(
+            config.export_vox.is_some() == config.export_region.is_some(),
+            "--export-vox and --export-region go together"
+        );
+
+        Ok(config)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            record_path: None,
+            play_path: None,
+            headless: false,
+            compare_layouts: false,
+            build_benchmark: false,
+            resolution: glam::uvec2(1280, 720),
+            screenshot_scale: 4,
+            mesh_path: None,
+            vox_path: None,
+            import_vox: None,
+            voxelize: None,
+            voxelize_resolution: 64,
+            import_origin: glam::ivec3(0, 16, 0),
+            export_vox: None,
+            export_region: None,
+            render: RenderParams::default(),
+            camera: CameraParams::default(),
+            world: WorldParams::default(),
+        }
+    }
+}
+
+fn parse_resolution(value: &str) -> anyhow::Result<glam::UVec2> {
+    let (w, h) = value
+        .split_once('x')
+        .with_context(|| format!("Invalid resolution {value}, expected WxH"))?;
+    let resolution = glam::uvec2(w.parse()?, h.parse()?);
+
+    anyhow::ensure!(
+        resolution.x > 0 && resolution.y > 0,
+        "Invalid resolution {value}"
+    );
+    Ok(resolution)
+}
+
+fn parse_ivec3(value: &str) -> anyhow::Result<glam::IVec3> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<Vec<i32>, _>>()?;
+    match values[..] {
+        [x, y, z] => Ok(glam::ivec3(x, y, z)),
+        _ => anyhow::bail!("Invalid coordinates {value}, expected x,y,z"),
+    }
+}
+
+// x0,y0,z0,x1,y1,z1 -> (min, max)
+fn parse_region(value: &str) -> anyhow::Result<(glam::IVec3, glam::IVec3)> {
+    let (min, max) = value
+        .match_indices(',')
+        .nth(2)
+        .map(|(i, _)| (&value[..i], &value[i + 1..]))
+        .with_context(|| format!("Invalid region {value}, expected x0,y0,z0,x1,y1,z1"))?;
+    let (min, max) = (parse_ivec3(min)?, parse_ivec3(max)?);
+
+    anyhow::ensure!(min.cmplt(max).all(), "Empty region {value}");
+    Ok((min, max))
+}
+
+fn parse_upscale_filter(value: &str) -> anyhow::Result<UpscaleFilter> {
+    match value {
+        "nearest" => Ok(UpscaleFilter::Nearest),
+        "bilinear" => Ok(UpscaleFilter::Bilinear),
+        _ => anyhow::bail!("Unknown upscale filter {value}"),
+    }
+}
+
+fn parse_raymarch_mode(value: &str) -> anyhow::Result<RaymarchMode> {
+    match value {
+        "fragment" => Ok(RaymarchMode::Fragment),
+        "compute" => Ok(RaymarchMode::Compute),
+        _ => anyhow::bail!("Unknown raymarch mode {value}"),
+    }
+}
+
+fn parse_chunk_structure(value: &str) -> anyhow::Result<ChunkStructure> {
+    match value {
+        "octree" => Ok(ChunkStructure::Octree),
+        "brickmap" => Ok(ChunkStructure::Brickmap),
+        _ => anyhow::bail!("Unknown chunk structure {value}"),
+    }
+}
+
+fn parse_svo_layout(value: &str) -> anyhow::Result<SvoLayout> {
+    match value {
+        "insertion" => Ok(SvoLayout::Insertion),
+        "bfs" => Ok(SvoLayout::Morton(NodeLayout::BreadthFirst)),
+        "dfs" => Ok(SvoLayout::Morton(NodeLayout::DepthFirst)),
+        _ => anyhow::bail!("Unknown SVO layout {value}"),
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{chunk::CHUNK_SIZE, gpu_context::DEPTH_FORMAT, world_position::WorldPosition};
+
+const SPHERE_SEGMENTS: u32 = 24; // Per circle
+const MAX_VERTICES: usize = 1 << 20; // ~28 MB, the extra lines are dropped
+
+// Immediate mode debug lines, cleared & filled again every frame. Relative to the min corner of
+// `origin` (a chunk near the camera), so they stay precise far from the world origin
+#[derive(Default)]
+pub struct DebugDraw {
+    origin: glam::IVec3,
+    vertices: Vec<DebugVertex>,
+    overflow: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+// Line list pass drawn over the upscaled frame, depth tested against the voxels
+pub struct DebugDrawPass {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl DebugDraw {
+    pub fn clear(&mut self, origin: glam::IVec3) {
+        self.origin = origin;
+        self.vertices.clear();
+        self.overflow = false;
+    }
+
+    pub fn line(&mut self, a: WorldPosition, b: WorldPosition, color: glam::Vec4) {
+        self.relative_line(
+            a.relative_to(self.origin),
+            b.relative_to(self.origin),
+            color,
+        );
+    }
+
+    pub fn aabb(&mut self, min: WorldPosition, max: WorldPosition, color: glam::Vec4) {
+        let (min, max) = (min.relative_to(self.origin), max.relative_to(self.origin));
+        let corner = |i: u32| {
+            glam::Vec3::select(
+                glam::BVec3::new(i & 4 != 0, i & 2 != 0, i & 1 != 0),
+                max,
+                min,
+            )
+        };
+
+        // Corners differing by one bit share an edge
+        for i in 0..8 {
+            for bit in [4, 2, 1] {
+                if i & bit == 0 {
+                    self.relative_line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    // One circle per axis plane
+    pub fn sphere(&mut self, center: WorldPosition, radius: f32, color: glam::Vec4) {
+        let center = center.relative_to(self.origin);
+        let point = |axis: usize, angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let offset = match axis {
+                0 => glam::vec3(0.0, cos, sin),
+                1 => glam::vec3(cos, 0.0, sin),
+                _ => glam::vec3(cos, sin, 0.0),
+            };
+            center + offset * radius
+        };
+
+        let step = std::f32::consts::TAU / SPHERE_SEGMENTS as f32;
+        for axis in 0..3 {
+            for i in 0..SPHERE_SEGMENTS {
+                let angle = i as f32 * step;
+                self.relative_line(point(axis, angle), point(axis, angle + step), color);
+            }
+        }
+    }
+
+    // Ends relative to `origin`
+    fn relative_line(&mut self, a: glam::Vec3, b: glam::Vec3, color: glam::Vec4) {
+        if self.vertices.len() + 2 > MAX_VERTICES {
+            if !self.overflow {
+                log::warn!("Debug draw is full, lines dropped");
+                self.overflow = true;
+            }
+            return;
+        }
+
+        let color = color.to_array();
+        self.vertices.push(DebugVertex {
+            position: a.to_array(),
+            color,
+        });
+        self.vertices.push(DebugVertex {
+            position: b.to_array(),
+            color,
+        });
+    }
+}
+
+impl DebugVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl DebugDrawPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("debug_draw.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Draw Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[DebugVertex::layout()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // Hidden by the voxels, but don't occlude each other
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let vertex_buffer = Self::create_vertex_buffer(device, 1024);
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        debug_draw: &DebugDraw,
+        render_origin: glam::IVec3,
+    ) {
+        // Usually the same chunk, else whole chunks apart
+        let origin = ((render_origin - debug_draw.origin) * CHUNK_SIZE as i32).as_vec3();
+        let vertices: Vec<_> = debug_draw
+            .vertices
+            .iter()
+            .map(|vertex| DebugVertex {
+                position: (glam::Vec3::from(vertex.position) - origin).to_array(),
+                ..*vertex
+            })
+            .collect();
+        self.vertex_count = vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+
+        let size = size_of_val(vertices.as_slice()) as wgpu::BufferAddress;
+        if size > self.vertex_buffer.size() {
+            self.vertex_buffer =
+                Self::create_vertex_buffer(device, vertices.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Draw Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Draw Vertex Buffer"),
+            size: (capacity * size_of::<DebugVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
@@ -0,0 +1,45 @@
+// ===========================
+// Structures
+// ===========================
+
+struct CameraUniform {
+    inv_view_proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    origin: vec3<f32>,
+    is_orthographic: u32,
+    medium: u32,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// ===========================
+// Bindings
+// ===========================
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// ===========================
+// Entry points
+// ===========================
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,120 @@
+use crate::{
+    chunk::CHUNK_SIZE,
+    terrain::{Biome, Terrain},
+    voxel::Voxel,
+};
+
+const CELL_SIZE: i32 = 8; // Columns per placement cell side, at most one feature per cell
+const CELL_MARGIN: i32 = 2; // Columns along the cell borders without roots, at least the feature reach
+const CANOPY_RADIUS: i32 = 2;
+const TRUNK: glam::Vec3 = glam::Vec3::new(0.4, 0.27, 0.15);
+const LEAVES: glam::Vec3 = glam::Vec3::new(0.15, 0.5, 0.15);
+const BOULDER: glam::Vec3 = glam::Vec3::new(0.48, 0.48, 0.5);
+
+// Multi-voxel structure standing on the terrain surface, it can reach into the neighbour chunks
+#[derive(Debug, Clone, Copy)]
+enum Feature {
+    Tree { trunk_height: i32 },
+    Boulder { radius: i32 },
+}
+
+// Voxels of the features rooted in the columns of the chunk, in world space. The placement only
+// depends on the root column, so a feature is the same whatever the order the chunks load in
+pub fn features(terrain: &Terrain, chunk_coord: glam::IVec3) -> Vec<(glam::IVec3, Voxel)> {
+    let mut voxels = Vec::new();
+    let cells = CHUNK_SIZE as i32 / CELL_SIZE;
+    for cx in 0..cells {
+        for cz in 0..cells {
+            let cell = glam::ivec2(chunk_coord.x * cells + cx, chunk_coord.z * cells + cz);
+            if let Some((root, feature)) = feature_in_cell(terrain, cell) {
+                feature.voxels(root, &mut voxels);
+            }
+        }
+    }
+    voxels
+}
+
+fn feature_in_cell(terrain: &Terrain, cell: glam::IVec2) -> Option<(glam::IVec3, Feature)> {
+    let hash = |salt| terrain.hash(cell.x, cell.y, salt);
+
+    // Away from the cell borders, so the features of neighbour cells never overlap
+    let roots = (CELL_SIZE - 2 * CELL_MARGIN) as f64;
+    let x = cell.x * CELL_SIZE + CELL_MARGIN + (hash(1) * roots) as i32;
+    let z = cell.y * CELL_SIZE + CELL_MARGIN + (hash(2) * roots) as i32;
+    let column = terrain.column(x, z);
+    if column.is_underwater() {
+        return None;
+    }
+    let root = glam::ivec3(x, column.height as i32, z); // First air voxel above the surface
+
+    let (tree_chance, boulder_chance) = match column.biome {
+        Biome::Forest => (0.8, 0.05),
+        Biome::Plains => (0.1, 0.1),
+        Biome::Desert => (0.0, 0.05),
+        Biome::Snow => (0.0, 0.2),
+    };
+    let roll = hash(3);
+    let feature = if roll < tree_chance {
+        Feature::Tree {
+            trunk_height: 4 + (hash(4) * 3.0) as i32,
+        }
+    } else if roll < tree_chance + boulder_chance {
+        Feature::Boulder {
+            radius: 1 + (hash(4) * 2.0) as i32,
+        }
+    } else {
+        return None;
+    };
+    debug_assert!(
+        feature.reach() <= CELL_MARGIN,
+        "{feature:?} crosses its cell"
+    );
+    Some((root, feature))
+}
+
+impl Feature {
+    // Columns it covers on each side of its root
+    fn reach(self) -> i32 {
+        match self {
+            Feature::Tree { .. } => CANOPY_RADIUS,
+            Feature::Boulder { radius } => radius,
+        }
+    }
+
+    fn voxels(self, root: glam::IVec3, voxels: &mut Vec<(glam::IVec3, Voxel)>) {
+        match self {
+            Feature::Tree { trunk_height } => {
+                for y in 0..trunk_height {
+                    voxels.push((root + glam::IVec3::Y * y, Voxel::new(TRUNK, true)));
+                }
+                // Rounded canopy around the top of the trunk
+                let top = root + glam::IVec3::Y * (trunk_height - 1);
+                for offset in box_offsets(CANOPY_RADIUS) {
+                    let trunk = (offset.x, offset.z) == (0, 0) && offset.y <= 0;
+                    if offset.length_squared() <= CANOPY_RADIUS * CANOPY_RADIUS + 1 && !trunk {
+                        voxels.push((top + offset, Voxel::new(LEAVES, true)));
+                    }
+                }
+            }
+            Feature::Boulder { radius } => {
+                // Half buried
+                for offset in box_offsets(radius) {
+                    if offset.length_squared() <= radius * radius + 1 {
+                        voxels.push((root + offset, Voxel::new(BOULDER, true)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Offsets in [-radius, radius]³
+fn box_offsets(radius: i32) -> impl Iterator<Item = glam::IVec3> {
+    let range = -radius..=radius;
+    range.clone().flat_map(move |x| {
+        let range = range.clone();
+        range
+            .clone()
+            .flat_map(move |y| range.clone().map(move |z| glam::ivec3(x, y, z)))
+    })
+}
@@ -0,0 +1,227 @@
+use std::ops::Range;
+
+use crate::{
+    gpu_context::DEPTH_FORMAT,
+    mesh::{Mesh, MeshData, MeshVertex},
+    world_position::WorldPosition,
+};
+
+pub type MeshId = usize;
+
+// Rasterized object placed in the voxel world
+pub struct Entity {
+    pub mesh: MeshId,
+    pub position: WorldPosition,
+    pub rotation: glam::Quat,
+    pub scale: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EntityInstance {
+    model: [[f32; 4]; 4],
+}
+
+// Draws the entities over the upscaled frame, one instanced draw per mesh
+pub struct EntityPass {
+    pipeline: wgpu::RenderPipeline,
+    meshes: Vec<Mesh>,
+    instance_buffer: wgpu::Buffer,
+    batches: Vec<(MeshId, Range<u32>)>, // Instances of each mesh in the instance buffer
+}
+
+impl Entity {
+    pub fn new(mesh: MeshId, position: WorldPosition) -> Self {
+        Self {
+            mesh,
+            position,
+            rotation: glam::Quat::IDENTITY,
+            scale: 1.0,
+        }
+    }
+
+    // In the render space of `render_origin`
+    fn model_matrix(&self, render_origin: glam::IVec3) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::splat(self.scale),
+            self.rotation,
+            self.position.relative_to(render_origin),
+        )
+    }
+}
+
+impl EntityInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4
+    ];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl EntityPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("entity.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Entity Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Entity Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[MeshVertex::layout(), EntityInstance::layout()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let instance_buffer = Self::create_instance_buffer(device, 64);
+
+        Self {
+            pipeline,
+            meshes: Vec::new(),
+            instance_buffer,
+            batches: Vec::new(),
+        }
+    }
+
+    pub fn add_mesh(&mut self, device: &wgpu::Device, data: &MeshData) -> MeshId {
+        self.meshes.push(Mesh::new(device, data));
+        self.meshes.len() - 1
+    }
+
+    // Rebuild the instance buffer, grouped by mesh
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+        render_origin: glam::IVec3,
+    ) {
+        let mut sorted: Vec<&Entity> = entities
+            .iter()
+            .filter(|entity| entity.mesh < self.meshes.len())
+            .collect();
+        sorted.sort_by_key(|entity| entity.mesh);
+
+        self.batches.clear();
+        let mut instances = Vec::with_capacity(sorted.len());
+        for (i, entity) in sorted.iter().enumerate() {
+            let i = i as u32;
+            match self.batches.last_mut() {
+                Some((mesh, range)) if *mesh == entity.mesh => range.end = i + 1,
+                _ => self.batches.push((entity.mesh, i..i + 1)),
+            }
+            instances.push(EntityInstance {
+                model: entity.model_matrix(render_origin).to_cols_array_2d(),
+            });
+        }
+
+        if instances.is_empty() {
+            return;
+        }
+        let size = size_of_val(instances.as_slice()) as wgpu::BufferAddress;
+        if size > self.instance_buffer.size() {
+            self.instance_buffer =
+                Self::create_instance_buffer(device, instances.len().next_power_of_two());
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Entity Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+
+        for (mesh_id, instances) in &self.batches {
+            let mesh = &self.meshes[*mesh_id];
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.index_count, 0, instances.clone());
+        }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Entity Instance Buffer"),
+            size: (capacity * size_of::<EntityInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
@@ -0,0 +1,65 @@
+// ===========================
+// Structures
+// ===========================
+
+struct CameraUniform {
+    inv_view_proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    origin: vec3<f32>,
+    is_orthographic: u32,
+    medium: u32,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(3) model_0: vec4<f32>,
+    @location(4) model_1: vec4<f32>,
+    @location(5) model_2: vec4<f32>,
+    @location(6) model_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+// ===========================
+// Constants
+// ===========================
+
+const SUN_DIRECTION: vec3<f32> = vec3<f32>(0.4, 1.0, 0.3);
+const AMBIENT: f32 = 0.3;
+
+// ===========================
+// Bindings
+// ===========================
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// ===========================
+// Entry points
+// ===========================
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model * vec4<f32>(vertex.position, 1.0);
+    out.normal = (model * vec4<f32>(vertex.normal, 0.0)).xyz; // Uniform scales only
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let diffuse = max(dot(normalize(in.normal), normalize(SUN_DIRECTION)), 0.0);
+    return vec4<f32>(in.color * (AMBIENT + (1.0 - AMBIENT) * diffuse), 1.0);
+}
@@ -2,45 +2,33 @@ use std::sync::Arc;
 use winit::window::{CursorGrabMode, Window};
 
 pub struct GpuContext {
-    pub instance: wgpu::Instance,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
 }
 
+// Depth of the final image, for the rasterized content drawn over the voxels
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 pub struct WindowSurface {
     pub window: Arc<Window>,
     pub surface: wgpu::Surface<'static>,
     pub config: wgpu::SurfaceConfiguration,
+    pub depth_view: wgpu::TextureView,
 }
 
 impl GpuContext {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<(Self, WindowSurface)> {
+    pub async fn new(window: Arc<Window>, vsync: bool) -> anyhow::Result<(Self, WindowSurface)> {
         let size = window.inner_size();
 
         // The instance is a handle to the GPU
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
         let surface = instance.create_surface(window.clone()).unwrap();
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-            })
-            .await?; // Wait the GPU response (asynchronous function)
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                experimental_features: wgpu::ExperimentalFeatures::disabled(),
-                ..Default::default()
-            })
-            .await?;
+        let gpu = Self::request(&instance, Some(&surface)).await?;
 
         // Configure the surface (the screen)
-        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_caps = surface.get_capabilities(&gpu.adapter);
         let surface_format = surface_caps
             .formats
             .iter()
@@ -53,27 +41,62 @@ impl GpuContext {
             format: surface_format,           // Preferred sRGB
             height: size.height,
             width: size.width,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: if vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                wgpu::PresentMode::AutoNoVsync // Benchmarks must not be capped by the refresh rate
+            },
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT, // Define the surface as a canvas
             view_formats: vec![],
         };
 
-        surface.configure(&device, &config);
+        surface.configure(&gpu.device, &config);
+        let depth_view = create_depth_view(&gpu.device, glam::uvec2(size.width, size.height));
 
         Ok((
-            Self {
-                instance,
-                adapter,
-                device,
-                queue,
-            },
+            gpu,
             WindowSurface {
                 window,
                 surface,
                 config,
+                depth_view,
             },
         ))
     }
+
+    // GPU without any window, to render in offscreen textures only
+    pub async fn new_headless() -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        Self::request(&instance, None).await
+    }
+
+    async fn request(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+    ) -> anyhow::Result<Self> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface,
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+            })
+            .await?; // Wait the GPU response (asynchronous function)
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                experimental_features: wgpu::ExperimentalFeatures::disabled(),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+        })
+    }
 }
 
 impl WindowSurface {
@@ -82,6 +105,7 @@ impl WindowSurface {
             self.config.width = width;
             self.config.height = height;
             self.surface.configure(device, &self.config);
+            self.depth_view = create_depth_view(device, glam::uvec2(width, height));
         }
     }
 
@@ -98,3 +122,22 @@ impl WindowSurface {
         }
     }
 }
+
+pub fn create_depth_view(device: &wgpu::Device, size: glam::UVec2) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width: size.x.max(1),
+            height: size.y.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
@@ -0,0 +1,97 @@
+use anyhow::Context;
+use std::time::Instant;
+
+use crate::{
+    benchmark::{Benchmark, BenchmarkReport},
+    camera_path::CameraPath,
+    config::Config,
+    gpu_context::{self, GpuContext},
+    player::Player,
+    renderer::Renderer,
+    world::World,
+};
+
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Play the camera path without any window, each frame waits for the GPU to finish
+pub fn run(config: &Config) -> anyhow::Result<()> {
+    let gpu = pollster::block_on(GpuContext::new_headless())?;
+
+    if !config.compare_layouts {
+        println!("{}", play(&gpu, config)?);
+        return Ok(());
+    }
+
+    // Same path & world, only the chunk structures change
+    for params in config.world.structure_variants() {
+        let name = params.structure_name();
+        let config = Config {
+            world: params,
+            ..config.clone()
+        };
+        println!("{name}: {}", play(&gpu, &config)?);
+    }
+    Ok(())
+}
+
+fn play(gpu: &GpuContext, config: &Config) -> anyhow::Result<BenchmarkReport> {
+    let play_path = config
+        .play_path
+        .as_deref()
+        .context("Headless mode requires a camera path")?;
+    let mut benchmark = Benchmark::new(CameraPath::load(play_path)?);
+
+    let world = World::from_config(config, false)?;
+    let mut player = Player::new(world.params.spawn_position(), config.camera);
+    let size = config.resolution;
+    let mut renderer = Renderer::new(
+        &gpu.device,
+        &gpu.queue,
+        TARGET_FORMAT,
+        size,
+        &config.render,
+        &player.camera,
+        &world,
+    );
+
+    let target = gpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Target"),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TARGET_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth_view = gpu_context::create_depth_view(&gpu.device, size);
+
+    while let Some(pose) = benchmark.next_pose() {
+        let start = Instant::now();
+
+        player.apply_pose(&pose);
+        player.camera.medium = world.medium_at(player.camera.position);
+        player.camera.update(size.x as usize, size.y as usize);
+        renderer.update_camera(&gpu.queue, &player.camera);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless Encoder"),
+            });
+        renderer.render(&mut encoder, &view, &depth_view);
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        gpu.device.poll(wgpu::PollType::wait_indefinitely())?;
+
+        let frame_time = start.elapsed();
+        benchmark.record_frame(frame_time);
+        renderer.update_frame_time(&gpu.queue, frame_time);
+    }
+
+    Ok(benchmark.report())
+}
@@ -1,20 +1,58 @@
-use crate::app::App;
+use crate::{app::App, config::Config, world::World};
 use winit::event_loop::EventLoop;
 
 mod app;
+mod benchmark;
+mod brickmap;
+mod build_benchmark;
 mod camera;
+mod camera_path;
 mod chunk;
+mod chunk_generator;
+mod chunk_pool;
+mod config;
+mod debug_draw;
+mod decoration;
+mod entity;
 mod gpu_context;
+mod headless;
+mod mesh;
+mod obj;
+mod palette;
 mod player;
 mod player_controller;
+mod raymarch;
+mod renderer;
+mod screenshot;
 mod state;
 mod svo;
+mod terrain;
+mod upscale;
+mod vox;
 mod voxel;
+mod voxel_object;
+mod voxelize;
 mod world;
+mod world_position;
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     env_logger::init();
+    let config = Config::from_args()?;
+
+    if config.build_benchmark {
+        return build_benchmark::run(&config);
+    }
+
+    if let (Some(path), Some((min, max))) = (&config.export_vox, config.export_region) {
+        return World::from_config(&config, false)?.export_vox(path, min, max);
+    }
+
+    if config.headless {
+        return headless::run(&config);
+    }
+
     let event_loop = EventLoop::with_user_event().build().unwrap();
-    let mut app = App::new(&event_loop);
+    let mut app = App::new(config);
     event_loop.run_app(&mut app).unwrap();
+    Ok(())
 }
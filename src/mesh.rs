@@ -0,0 +1,153 @@
+use std::path::Path;
+use wgpu::util::DeviceExt;
+
+use crate::obj::Obj;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 3],
+}
+
+// Triangle list on the CPU side
+pub struct MeshData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+// Triangle list uploaded to the GPU
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_count: u32,
+}
+
+impl MeshVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl MeshData {
+    // All the models of the file are merged, the color comes from the vertex colors or the diffuse material
+    pub fn load_obj(path: &Path) -> anyhow::Result<Self> {
+        let obj = Obj::load(path)?;
+        let mut data = Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+
+        for model in &obj.models {
+            let mesh = &model.mesh;
+            let first_vertex = data.vertices.len() as u32;
+            let diffuse = obj.diffuse(mesh).to_array();
+
+            for i in 0..mesh.positions.len() / 3 {
+                let vec3 = |values: &[f32]| -> Option<[f32; 3]> {
+                    values.get(i * 3..i * 3 + 3).map(|v| [v[0], v[1], v[2]])
+                };
+
+                data.vertices.push(MeshVertex {
+                    position: vec3(&mesh.positions).unwrap(),
+                    normal: vec3(&mesh.normals).unwrap_or_default(),
+                    color: vec3(&mesh.vertex_color).unwrap_or(diffuse),
+                });
+            }
+            data.indices
+                .extend(mesh.indices.iter().map(|index| first_vertex + index));
+
+            if mesh.normals.is_empty() {
+                data.compute_normals(first_vertex as usize);
+            }
+        }
+
+        anyhow::ensure!(
+            !data.indices.is_empty(),
+            "{} has no triangles",
+            path.display()
+        );
+        Ok(data)
+    }
+
+    // Unit cube centered on the origin
+    pub fn cube(color: glam::Vec3) -> Self {
+        let mut data = Self {
+            vertices: Vec::with_capacity(24),
+            indices: Vec::with_capacity(36),
+        };
+
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let normal = glam::Vec3::AXES[axis] * sign;
+                let u = glam::Vec3::AXES[(axis + 1) % 3];
+                let v = normal.cross(u);
+
+                let first_vertex = data.vertices.len() as u32;
+                for (du, dv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                    data.vertices.push(MeshVertex {
+                        position: ((normal + u * du + v * dv) * 0.5).to_array(),
+                        normal: normal.to_array(),
+                        color: color.to_array(),
+                    });
+                }
+                data.indices
+                    .extend([0, 1, 2, 0, 2, 3].map(|i| first_vertex + i));
+            }
+        }
+
+        data
+    }
+
+    // Smooth normals from the faces, for the vertices from `first_vertex`
+    fn compute_normals(&mut self, first_vertex: usize) {
+        let mut normals = vec![glam::Vec3::ZERO; self.vertices.len() - first_vertex];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            if a < first_vertex || b < first_vertex || c < first_vertex {
+                continue;
+            }
+
+            let position = |i: usize| glam::Vec3::from(self.vertices[i].position);
+            // Not normalized, so bigger faces weigh more
+            let normal = (position(b) - position(a)).cross(position(c) - position(a));
+            for i in [a, b, c] {
+                normals[i - first_vertex] += normal;
+            }
+        }
+
+        for (vertex, normal) in self.vertices[first_vertex..].iter_mut().zip(normals) {
+            vertex.normal = normal.normalize_or_zero().to_array();
+        }
+    }
+}
+
+impl Mesh {
+    pub fn new(device: &wgpu::Device, data: &MeshData) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(&data.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(&data.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: data.indices.len() as u32,
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::Context;
+use std::path::Path;
+
+pub const DEFAULT_COLOR: glam::Vec3 = glam::Vec3::splat(0.8); // When the OBJ has no vertex colors nor material
+
+// Triangulated models of an OBJ file, one index per vertex for the positions, normals, uvs & colors
+pub struct Obj {
+    pub models: Vec<tobj::Model>,
+    pub materials: Vec<tobj::Material>, // Empty if the .mtl is missing or broken, the colors fall back
+}
+
+impl Obj {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, materials) = tobj::load_obj(path, &options)
+            .with_context(|| format!("Cannot load {}", path.display()))?;
+        let materials = materials.unwrap_or_else(|e| {
+            log::warn!("No materials for {}: {e}", path.display());
+            Vec::new()
+        });
+        Ok(Self { models, materials })
+    }
+
+    fn material(&self, mesh: &tobj::Mesh) -> Option<&tobj::Material> {
+        mesh.material_id.and_then(|id| self.materials.get(id))
+    }
+
+    // Of the mesh material, DEFAULT_COLOR without one
+    pub fn diffuse(&self, mesh: &tobj::Mesh) -> glam::Vec3 {
+        self.material(mesh)
+            .and_then(|material| material.diffuse)
+            .map_or(DEFAULT_COLOR, glam::Vec3::from)
+    }
+}
@@ -0,0 +1,259 @@
+use crate::voxel::Voxel;
+
+// Fixed number of voxels: a single value while they are all the same, else indices in a palette of
+// the distinct voxels, packed on the fewest bits (a power of 2, so no index straddles 2 words)
+pub struct PalettedVoxels {
+    len: usize,
+    storage: Storage,
+}
+
+enum Storage {
+    Uniform(Voxel),
+    Paletted {
+        palette: Vec<Voxel>,
+        counts: Vec<usize>, // Voxels using each palette entry, the unused entries are reused
+        bits: u32,
+        words: Vec<u64>,
+    },
+}
+
+impl PalettedVoxels {
+    pub fn new(len: usize, voxel: Voxel) -> Self {
+        Self {
+            len,
+            storage: Storage::Uniform(voxel),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Voxel {
+        debug_assert!(index < self.len);
+        match &self.storage {
+            Storage::Uniform(voxel) => *voxel,
+            Storage::Paletted {
+                palette,
+                bits,
+                words,
+                ..
+            } => palette[read_index(words, *bits, index)],
+        }
+    }
+
+    pub fn set(&mut self, index: usize, voxel: Voxel) {
+        debug_assert!(index < self.len);
+        let len = self.len;
+
+        if let Storage::Uniform(value) = self.storage {
+            if value == voxel {
+                return;
+            }
+            // Everything on the first entry
+            self.storage = Storage::Paletted {
+                palette: vec![value],
+                counts: vec![len],
+                bits: 1,
+                words: vec![0; len.div_ceil(64)],
+            };
+        }
+        let Storage::Paletted {
+            palette,
+            counts,
+            bits,
+            words,
+        } = &mut self.storage
+        else {
+            unreachable!()
+        };
+
+        let old = read_index(words, *bits, index);
+        if palette[old] == voxel {
+            return;
+        }
+
+        let new = match palette.iter().position(|entry| *entry == voxel) {
+            Some(new) => new,
+            None => match counts.iter().position(|count| *count == 0) {
+                Some(unused) => {
+                    palette[unused] = voxel;
+                    unused
+                }
+                None => {
+                    palette.push(voxel);
+                    counts.push(0);
+                    if palette.len() > 1 << *bits {
+                        *words = repack(words, *bits, *bits * 2, len);
+                        *bits *= 2;
+                    }
+                    palette.len() - 1
+                }
+            },
+        };
+
+        counts[old] -= 1;
+        counts[new] += 1;
+        if counts[new] == len {
+            self.storage = Storage::Uniform(voxel);
+            return;
+        }
+        write_index(words, *bits, index, new);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Voxel> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+
+    pub fn uniform(&self) -> Option<Voxel> {
+        match self.storage {
+            Storage::Uniform(voxel) => Some(voxel),
+            Storage::Paletted { .. } => None,
+        }
+    }
+
+    // Heap & inline bytes
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + match &self.storage {
+                Storage::Uniform(_) => 0,
+                Storage::Paletted {
+                    palette,
+                    counts,
+                    words,
+                    ..
+                } => {
+                    size_of_val(palette.as_slice())
+                        + size_of_val(counts.as_slice())
+                        + size_of_val(words.as_slice())
+                }
+            }
+    }
+}
+
+fn read_index(words: &[u64], bits: u32, index: usize) -> usize {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    ((words[index / per_word] >> shift) & ((1 << bits) - 1)) as usize
+}
+
+fn write_index(words: &mut [u64], bits: u32, index: usize, value: usize) {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    let word = &mut words[index / per_word];
+    *word = (*word & !(((1 << bits) - 1) << shift)) | ((value as u64) << shift);
+}
+
+fn repack(words: &[u64], bits: u32, new_bits: u32, len: usize) -> Vec<u64> {
+    let mut repacked = vec![0; (len * new_bits as usize).div_ceil(64)];
+    for index in 0..len {
+        write_index(
+            &mut repacked,
+            new_bits,
+            index,
+            read_index(words, bits, index),
+        );
+    }
+    repacked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 4096;
+
+    fn voxel(i: usize) -> Voxel {
+        Voxel {
+            color: 0xFF000000 | i as u32,
+        }
+    }
+
+    fn bits(voxels: &PalettedVoxels) -> u32 {
+        match voxels.storage {
+            Storage::Uniform(_) => 0,
+            Storage::Paletted { bits, .. } => bits,
+        }
+    }
+
+    fn palette_len(voxels: &PalettedVoxels) -> usize {
+        match &voxels.storage {
+            Storage::Uniform(_) => 1,
+            Storage::Paletted { palette, .. } => palette.len(),
+        }
+    }
+
+    #[test]
+    fn indices_widen_as_the_palette_grows() {
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::default());
+        assert_eq!(bits(&voxels), 0);
+
+        // The air entry plus `distinct` voxels
+        for (distinct, expected_bits) in [(1, 1), (3, 2), (15, 4), (255, 8), (256, 16)] {
+            for i in 0..distinct {
+                voxels.set(i, voxel(i + 1));
+            }
+            assert_eq!(bits(&voxels), expected_bits, "{distinct} distinct voxels");
+            for i in 0..distinct {
+                assert_eq!(voxels.get(i), voxel(i + 1));
+            }
+            assert_eq!(voxels.get(LEN - 1), Voxel::default());
+        }
+    }
+
+    #[test]
+    fn repacking_keeps_every_voxel() {
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::default());
+        let expected = |i: usize| voxel(i * 7 % 300);
+        for i in 0..LEN {
+            voxels.set(i, expected(i));
+        }
+
+        assert_eq!(bits(&voxels), 16);
+        assert!(voxels.iter().eq((0..LEN).map(expected)));
+    }
+
+    #[test]
+    fn filling_with_one_voxel_goes_back_to_uniform() {
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::default());
+        for i in 0..100 {
+            voxels.set(i, voxel(i % 5));
+        }
+        assert_eq!(voxels.uniform(), None);
+
+        for i in 0..LEN {
+            voxels.set(i, voxel(9));
+        }
+        assert_eq!(voxels.uniform(), Some(voxel(9)));
+        assert_eq!(voxels.get(0), voxel(9));
+    }
+
+    #[test]
+    fn unused_palette_entries_are_reused() {
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::default());
+        voxels.set(0, voxel(1));
+        voxels.set(1, voxel(2));
+        assert_eq!(palette_len(&voxels), 3);
+
+        // Nothing uses voxel(1) anymore, voxel(3) takes its entry
+        voxels.set(0, Voxel::default());
+        voxels.set(2, voxel(3));
+        assert_eq!(palette_len(&voxels), 3);
+        assert_eq!(bits(&voxels), 2);
+        assert_eq!(voxels.get(1), voxel(2));
+        assert_eq!(voxels.get(2), voxel(3));
+
+        // Already in the palette
+        voxels.set(3, voxel(2));
+        assert_eq!(palette_len(&voxels), 3);
+    }
+
+    #[test]
+    fn memory_size_counts_the_palette_and_the_indices() {
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::default());
+        assert_eq!(voxels.memory_size(), size_of::<PalettedVoxels>());
+
+        voxels.set(0, voxel(1));
+        let palette = 2 * size_of::<Voxel>() + 2 * size_of::<usize>();
+        assert_eq!(
+            voxels.memory_size(),
+            size_of::<PalettedVoxels>() + palette + LEN / 8
+        );
+    }
+}
@@ -1,17 +1,46 @@
-use crate::{camera::Camera, player_controller::PlayerController};
+use crate::{
+    camera::{Camera, CameraParams},
+    camera_path::CameraPose,
+    player_controller::PlayerController,
+    world_position::WorldPosition,
+};
+
+const HITBOX_SIZE: glam::Vec3 = glam::Vec3::new(0.6, 1.8, 0.6);
+const EYE_HEIGHT: f32 = 1.6;
 
 pub struct Player {
-    position: glam::Vec3,
+    position: WorldPosition,
     pub camera: Camera,
 }
 
 impl Player {
-    pub fn new(position: glam::Vec3) -> Self {
-        let camera = Camera::new(position);
+    pub fn new(position: WorldPosition, camera_params: CameraParams) -> Self {
+        let camera = Camera::new(position, camera_params);
 
         Self { position, camera }
     }
 
+    // (min, max) of the box around the player, the camera is at eye height (not used for collisions yet)
+    pub fn hitbox(&self) -> (WorldPosition, WorldPosition) {
+        let feet = self.position + glam::Vec3::NEG_Y * EYE_HEIGHT;
+        let half = HITBOX_SIZE * glam::vec3(0.5, 0.0, 0.5);
+        (feet + -half, feet + (half + glam::Vec3::Y * HITBOX_SIZE.y))
+    }
+
+    // Whether the hitbox overlaps the voxel, compared near the voxel so it stays exact far from the origin
+    pub fn overlaps_voxel(&self, voxel: glam::IVec3) -> bool {
+        let voxel = WorldPosition::from_voxel(voxel);
+        let (min, max) = self.hitbox();
+        let (min, max) = (min.relative_to(voxel.chunk), max.relative_to(voxel.chunk));
+        voxel.offset.cmplt(max).all() && (voxel.offset + 1.0).cmpgt(min).all()
+    }
+
+    pub fn apply_pose(&mut self, pose: &CameraPose) {
+        self.position = WorldPosition::from_dvec3(pose.position);
+        self.camera.position = self.position;
+        self.camera.set_orientation(pose.yaw, pose.pitch);
+    }
+
     pub fn move_player(
         &mut self,
         controller: &PlayerController,
@@ -51,3 +80,31 @@ impl Player {
         self.camera.position = self.position;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hitbox_is_precise_far_from_the_origin() {
+        for far in [1e6, -1e6] {
+            // Feet 1.6 below the eye, halfway up the voxel two below it
+            let eye = glam::dvec3(far + 0.5, far + 0.1, far + 0.5);
+            let player = Player::new(
+                WorldPosition::from_dvec3(eye),
+                CameraParams {
+                    fov: 60.0,
+                    ortho_scale: 64.0,
+                },
+            );
+            let (min, max) = player.hitbox();
+            let size = max.relative_to(min.chunk) - min.offset;
+            assert!((size - HITBOX_SIZE).abs().max_element() < 1e-5);
+
+            let feet = eye.floor().as_ivec3() - glam::IVec3::Y * 2;
+            assert!(player.overlaps_voxel(feet));
+            assert!(!player.overlaps_voxel(feet - glam::IVec3::Y));
+            assert!(!player.overlaps_voxel(feet + glam::IVec3::X));
+        }
+    }
+}
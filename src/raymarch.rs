@@ -0,0 +1,421 @@
+use wgpu::util::DeviceExt;
+
+use crate::{gpu_context::DEPTH_FORMAT, upscale::UpscalePass};
+
+// Format of the offscreen raymarch output, upscaled to the final target
+pub const RAYMARCH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// Storage textures can't be depth textures, the compute path writes the depth as a float
+const COMPUTE_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+const TILE_SIZE: u32 = 8; // Must match raymarch_compute.wgsl
+
+// How the SVO traversal is run, selected at startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaymarchMode {
+    Fragment, // Full screen triangle, one fragment per pixel
+    Compute,  // 8x8 tiles writing to a storage texture
+}
+
+pub enum RaymarchPass {
+    Fragment {
+        pipeline: wgpu::RenderPipeline,
+    },
+    Compute {
+        pipeline: wgpu::ComputePipeline,
+        target_layout: wgpu::BindGroupLayout,
+    },
+}
+
+// Offscreen texture the raymarch pass renders into, only the top-left `viewport` is used
+pub struct RaymarchTarget {
+    pub view: wgpu::TextureView,
+    pub depth_view: wgpu::TextureView, // NDC depth of the hits, 1.0 on a miss
+    pub size: glam::UVec2,
+    pub viewport: glam::UVec2,
+    loading_progress: Option<f32>,
+    uniform_buffer: wgpu::Buffer,
+    pub upscale_bind_group: wgpu::BindGroup,
+    compute_bind_group: Option<wgpu::BindGroup>,
+}
+
+#[repr(C, align(16))]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TargetUniform {
+    uv_scale: [f32; 2], // Viewport size / texture size
+    uv_max: [f32; 2],   // Last texel center of the viewport, to avoid bleeding outside of it
+    viewport: [u32; 2],
+    loading: u32, // Draw the loading bar over the frame in the upscale pass
+    loading_progress: f32,
+}
+
+impl RaymarchPass {
+    pub fn new(
+        device: &wgpu::Device,
+        mode: RaymarchMode,
+        camera_layout: &wgpu::BindGroupLayout,
+        world_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        match mode {
+            RaymarchMode::Fragment => Self::new_fragment(device, camera_layout, world_layout),
+            RaymarchMode::Compute => Self::new_compute(device, camera_layout, world_layout),
+        }
+    }
+
+    fn new_fragment(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        world_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Confiure the render pipeline
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Raymarch Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("svo_traversal.wgsl"),
+                    include_str!("shader.wgsl")
+                )
+                .into(),
+            ),
+        });
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    camera_layout, // @group(0)
+                    world_layout,  // @group(1)
+                ],
+                immediate_size: 0,
+            });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                // 3.
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    // 4.
+                    format: RAYMARCH_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // The depth comes from the ray hit, not from the triangle
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self::Fragment { pipeline }
+    }
+
+    fn new_compute(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        world_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Raymarch Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("svo_traversal.wgsl"),
+                    include_str!("raymarch_compute.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let target_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Raymarch Target Bind Group Layout"),
+            entries: &[
+                // Binding 0 -> Output storage texture
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: RAYMARCH_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                // Binding 1 -> Target Uniform
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Binding 2 -> Output depth
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: COMPUTE_DEPTH_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Raymarch Compute Pipeline Layout"),
+            bind_group_layouts: &[
+                camera_layout,  // @group(0)
+                world_layout,   // @group(1)
+                &target_layout, // @group(2)
+            ],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Raymarch Compute Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self::Compute {
+            pipeline,
+            target_layout,
+        }
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &RaymarchTarget,
+        camera_bind_group: &wgpu::BindGroup,
+        world_bind_group: &wgpu::BindGroup,
+    ) {
+        match self {
+            Self::Fragment { pipeline } => {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target.view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color {
+                                r: 0.1,
+                                g: 0.2,
+                                b: 0.3,
+                                a: 1.0,
+                            }),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &target.depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                    multiview_mask: None,
+                });
+
+                render_pass.set_viewport(
+                    0.0,
+                    0.0,
+                    target.viewport.x as f32,
+                    target.viewport.y as f32,
+                    0.0,
+                    1.0,
+                );
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, camera_bind_group, &[]);
+                render_pass.set_bind_group(1, world_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            Self::Compute { pipeline, .. } => {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Raymarch Compute Pass"),
+                    timestamp_writes: None,
+                });
+
+                let tiles_x = target.viewport.x.div_ceil(TILE_SIZE);
+                let tiles_y = target.viewport.y.div_ceil(TILE_SIZE);
+
+                compute_pass.set_pipeline(pipeline);
+                compute_pass.set_bind_group(0, camera_bind_group, &[]);
+                compute_pass.set_bind_group(1, world_bind_group, &[]);
+                compute_pass.set_bind_group(2, target.compute_bind_group.as_ref(), &[]);
+                compute_pass.dispatch_workgroups(tiles_x, tiles_y, 1);
+            }
+        }
+    }
+}
+
+impl RaymarchTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        size: glam::UVec2,
+        raymarch_pass: &RaymarchPass,
+        upscale_pass: &UpscalePass,
+    ) -> Self {
+        let size = size.max(glam::UVec2::ONE);
+
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING;
+        usage |= match raymarch_pass {
+            RaymarchPass::Fragment { .. } => wgpu::TextureUsages::RENDER_ATTACHMENT,
+            RaymarchPass::Compute { .. } => wgpu::TextureUsages::STORAGE_BINDING,
+        };
+        let depth_format = match raymarch_pass {
+            RaymarchPass::Fragment { .. } => DEPTH_FORMAT,
+            RaymarchPass::Compute { .. } => COMPUTE_DEPTH_FORMAT,
+        };
+
+        let view = Self::create_view(device, "Raymarch Target", size, RAYMARCH_FORMAT, usage);
+        let depth_view =
+            Self::create_view(device, "Raymarch Depth Target", size, depth_format, usage);
+
+        let uniform = TargetUniform::new(size, size, None);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Raymarch Target Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let upscale_bind_group =
+            upscale_pass.bind_group(device, &view, &depth_view, &uniform_buffer);
+        let compute_bind_group = match raymarch_pass {
+            RaymarchPass::Fragment { .. } => None,
+            RaymarchPass::Compute { target_layout, .. } => {
+                Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Raymarch Target Bind Group"),
+                    layout: target_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&depth_view),
+                        },
+                    ],
+                }))
+            }
+        };
+
+        Self {
+            view,
+            depth_view,
+            size,
+            viewport: size,
+            loading_progress: None,
+            uniform_buffer,
+            upscale_bind_group,
+            compute_bind_group,
+        }
+    }
+
+    fn create_view(
+        device: &wgpu::Device,
+        label: &str,
+        size: glam::UVec2,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // Render `scale` of the texture size, the rest of the texture is left untouched
+    pub fn set_scale(&mut self, queue: &wgpu::Queue, scale: f32) {
+        let viewport = (self.size.as_vec2() * scale)
+            .ceil()
+            .as_uvec2()
+            .clamp(glam::UVec2::ONE, self.size);
+
+        if viewport != self.viewport {
+            self.viewport = viewport;
+            self.write_uniform(queue);
+        }
+    }
+
+    // In [0, 1], None hides the loading bar
+    pub fn set_loading_progress(&mut self, queue: &wgpu::Queue, progress: Option<f32>) {
+        if progress != self.loading_progress {
+            self.loading_progress = progress;
+            self.write_uniform(queue);
+        }
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        let uniform = TargetUniform::new(self.size, self.viewport, self.loading_progress);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+}
+
+impl TargetUniform {
+    fn new(size: glam::UVec2, viewport: glam::UVec2, loading_progress: Option<f32>) -> Self {
+        let size_f = size.as_vec2();
+        let viewport_f = viewport.as_vec2();
+
+        Self {
+            uv_scale: (viewport_f / size_f).to_array(),
+            uv_max: ((viewport_f - 0.5) / size_f).to_array(),
+            viewport: viewport.to_array(),
+            loading: loading_progress.is_some() as u32,
+            loading_progress: loading_progress.unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// Compute raymarching path, appended to svo_traversal.wgsl
+
+// ===========================
+// Structures
+// ===========================
+
+struct TargetUniform {
+    uv_scale: vec2<f32>,
+    uv_max: vec2<f32>,
+    viewport: vec2<u32>, // Size in pixels of the area to render
+};
+
+// ===========================
+// Constants
+// ===========================
+
+const TILE_SIZE: u32 = 8;
+// First nodes of each pooled chunk. That's its top 4 levels (1 + 8 + 64 + 512) only with the
+// breadth-first layout, the other layouts cache the nodes allocated first
+const NODE_CACHE_SIZE: u32 = 585;
+
+// ===========================
+// Bindings
+// ===========================
+
+@group(2) @binding(0)
+var output: texture_storage_2d<rgba16float, write>;
+@group(2) @binding(1)
+var<uniform> target_params: TargetUniform;
+@group(2) @binding(2)
+var output_depth: texture_storage_2d<r32float, write>;
+
+// Copy of svo[cache_start .. cache_start + cache_len], the first nodes of the chunk the tile looks at
+var<workgroup> node_cache: array<SVONode, NODE_CACHE_SIZE>;
+var<workgroup> cache_start: u32;
+var<workgroup> cache_len: u32;
+
+// ===========================
+// SVO access
+// ===========================
+
+// Most rays of a tile enter the same chunk first & go through its top nodes, read them from the
+// workgroup memory
+fn fetch_node(idx: u32) -> SVONode {
+    let offset = idx - cache_start; // Wraps around below the start
+    if (offset < cache_len) {
+        return node_cache[offset];
+    }
+    return svo[idx];
+}
+
+// ===========================
+// Utility functions
+// ===========================
+
+fn pixel_ndc(pixel: vec2<f32>) -> vec2<f32> {
+    let uv = pixel / vec2<f32>(target_params.viewport);
+    return vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+}
+
+// Root of the first non empty chunk along the ray, EMPTY_CHUNK if there is none
+fn first_chunk_root(ray: Ray) -> u32 {
+    var march = chunk_march_start(ray);
+    while (!march.done) {
+        let root = chunk_root(march.dda.cell);
+        if (root != EMPTY_CHUNK) {
+            return root;
+        }
+        march.done = !dda_step(&march.dda, i32(world_params.grid_size));
+    }
+    return EMPTY_CHUNK;
+}
+
+// ===========================
+// Compute shader
+// ===========================
+
+@compute @workgroup_size(TILE_SIZE, TILE_SIZE)
+fn cs_main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    // The chunk seen by the center of the tile
+    if (local_index == 0u) {
+        let center = vec2<f32>(workgroup_id.xy * TILE_SIZE) + f32(TILE_SIZE) / 2.0;
+        let root = first_chunk_root(camera_ray(pixel_ndc(center)));
+        cache_start = root;
+        cache_len = select(min(NODE_CACHE_SIZE, arrayLength(&svo) - root), 0u, root == EMPTY_CHUNK);
+    }
+    workgroupBarrier();
+
+    // Every invocation of the tile loads a part of the cache
+    for (var i = local_index; i < cache_len; i += TILE_SIZE * TILE_SIZE) {
+        node_cache[i] = svo[cache_start + i];
+    }
+    workgroupBarrier();
+
+    // Tiles on the viewport border
+    if (any(global_id.xy >= target_params.viewport)) {
+        return;
+    }
+
+    let ray = camera_ray(pixel_ndc(vec2<f32>(global_id.xy) + 0.5));
+    let hit = trace(ray);
+
+    textureStore(output, global_id.xy, hit.color);
+    textureStore(output_depth, global_id.xy, vec4<f32>(hit_depth(ray, hit.t), 0.0, 0.0, 0.0));
+}
@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use crate::{
+    camera::{Camera, CameraResource},
+    debug_draw::{DebugDraw, DebugDrawPass},
+    entity::{Entity, EntityPass, MeshId},
+    gpu_context,
+    mesh::MeshData,
+    raymarch::{RaymarchMode, RaymarchPass, RaymarchTarget},
+    upscale::{DynamicResolution, UpscaleFilter, UpscalePass},
+    voxel_object::{VoxelModel, VoxelModelId, VoxelObject},
+    world::{World, WorldResource},
+};
+
+// Everything needed to draw a frame, independent from the window (shared by the windowed & headless paths)
+pub struct Renderer {
+    raymarch_pass: RaymarchPass,
+    upscale_pass: UpscalePass,
+    entity_pass: EntityPass,
+    debug_draw_pass: DebugDrawPass,
+    camera_resource: CameraResource,
+    world_resource: WorldResource,
+    render_origin: glam::IVec3, // Chunk of the camera, everything is uploaded relative to it
+
+    // Resolution scaling
+    target: RaymarchTarget,
+    params: RenderParams,
+    scale: f32,
+    dynamic_resolution: Option<DynamicResolution>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderParams {
+    pub scale: f32, // Raymarch resolution / output resolution, the upper bound with a frame budget
+    pub min_scale: f32, // Lower bound of the dynamic resolution
+    pub frame_budget: Option<Duration>, // Enable the dynamic resolution
+    pub upscale_filter: UpscaleFilter,
+    pub raymarch_mode: RaymarchMode,
+}
+
+impl Renderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        size: glam::UVec2,
+        params: &RenderParams,
+        camera: &Camera,
+        world: &World,
+    ) -> Self {
+        let camera_resource = CameraResource::new(device, camera);
+        let mut world_resource = WorldResource::new(device, &world.params);
+
+        let raymarch_pass = RaymarchPass::new(
+            device,
+            params.raymarch_mode,
+            &camera_resource.layout,
+            &world_resource.layout,
+        );
+
+        world_resource.upload(queue, world);
+        world_resource.set_render_origin(queue, camera.render_origin());
+
+        let upscale_pass = UpscalePass::new(device, format, params.upscale_filter);
+        let entity_pass = EntityPass::new(device, format, &camera_resource.layout);
+        let debug_draw_pass = DebugDrawPass::new(device, format, &camera_resource.layout);
+        let target = RaymarchTarget::new(
+            device,
+            Self::target_size(size, params.scale),
+            &raymarch_pass,
+            &upscale_pass,
+        );
+
+        Self {
+            raymarch_pass,
+            upscale_pass,
+            entity_pass,
+            debug_draw_pass,
+            camera_resource,
+            world_resource,
+            render_origin: camera.render_origin(),
+            target,
+            params: params.clone(),
+            scale: params.scale,
+            dynamic_resolution: params.frame_budget.map(DynamicResolution::new),
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: glam::UVec2) {
+        self.target = self.create_target(device, Self::target_size(size, self.params.scale));
+        self.target.set_scale(queue, self.scale / self.params.scale);
+    }
+
+    // Feed the dynamic resolution with the last frame time
+    pub fn update_frame_time(&mut self, queue: &wgpu::Queue, frame_time: Duration) {
+        if let Some(dynamic_resolution) = &mut self.dynamic_resolution {
+            self.scale = dynamic_resolution.update(
+                frame_time,
+                self.scale,
+                self.params.min_scale,
+                self.params.scale,
+            );
+            self.target.set_scale(queue, self.scale / self.params.scale);
+        }
+    }
+
+    pub fn set_loading_progress(&mut self, queue: &wgpu::Queue, progress: Option<f32>) {
+        self.target.set_loading_progress(queue, progress);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn update_camera(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        self.camera_resource.update(queue, camera);
+        if camera.render_origin() != self.render_origin {
+            self.render_origin = camera.render_origin();
+            self.world_resource
+                .set_render_origin(queue, self.render_origin);
+        }
+    }
+
+    pub fn add_mesh(&mut self, device: &wgpu::Device, data: &MeshData) -> MeshId {
+        self.entity_pass.add_mesh(device, data)
+    }
+
+    pub fn update_entities(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        entities: &[Entity],
+    ) {
+        self.entity_pass
+            .upload(device, queue, entities, self.render_origin);
+    }
+
+    pub fn add_voxel_model(
+        &mut self,
+        queue: &wgpu::Queue,
+        model: &VoxelModel,
+    ) -> anyhow::Result<VoxelModelId> {
+        self.world_resource.add_voxel_model(queue, model)
+    }
+
+    pub fn update_voxel_objects(&mut self, queue: &wgpu::Queue, objects: &[VoxelObject]) {
+        self.world_resource
+            .update_voxel_objects(queue, objects, self.render_origin);
+    }
+
+    pub fn update_chunks(
+        &mut self,
+        queue: &wgpu::Queue,
+        world: &World,
+        chunk_coords: &[glam::IVec3],
+    ) {
+        self.world_resource
+            .update_chunks(queue, world, chunk_coords);
+    }
+
+    pub fn set_heatmap(&mut self, queue: &wgpu::Queue, heatmap: bool) {
+        self.world_resource.set_heatmap(queue, heatmap);
+    }
+
+    pub fn update_debug_draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        debug_draw: &DebugDraw,
+    ) {
+        self.debug_draw_pass
+            .upload(device, queue, debug_draw, self.render_origin);
+    }
+
+    // `depth_view` must be the size of `view`, it holds the raymarched depth afterwards
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.raymarch(encoder, &self.target);
+        self.upscale_pass
+            .draw(encoder, &self.target, view, depth_view);
+        self.draw_overlays(encoder, view, depth_view);
+    }
+
+    // Render at full scale in a view of any size (screenshots)
+    pub fn render_offscreen(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: glam::UVec2,
+    ) {
+        let target = self.create_target(device, size);
+        let depth_view = gpu_context::create_depth_view(device, size);
+        self.raymarch(encoder, &target);
+        self.upscale_pass.draw(encoder, &target, view, &depth_view);
+        self.draw_overlays(encoder, view, &depth_view);
+    }
+
+    fn raymarch(&self, encoder: &mut wgpu::CommandEncoder, target: &RaymarchTarget) {
+        self.raymarch_pass.draw(
+            encoder,
+            target,
+            &self.camera_resource.bind_group,
+            &self.world_resource.bind_group,
+        );
+    }
+
+    // Rasterized content, depth tested against the upscaled raymarch depth
+    fn draw_overlays(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.entity_pass
+            .draw(encoder, view, depth_view, &self.camera_resource.bind_group);
+        self.debug_draw_pass
+            .draw(encoder, view, depth_view, &self.camera_resource.bind_group);
+    }
+
+    fn create_target(&self, device: &wgpu::Device, size: glam::UVec2) -> RaymarchTarget {
+        RaymarchTarget::new(device, size, &self.raymarch_pass, &self.upscale_pass)
+    }
+
+    fn target_size(size: glam::UVec2, scale: f32) -> glam::UVec2 {
+        (size.as_vec2() * scale).ceil().as_uvec2()
+    }
+}
+
+impl Default for RenderParams {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            min_scale: 0.5,
+            frame_budget: None,
+            upscale_filter: UpscaleFilter::Bilinear,
+            raymarch_mode: RaymarchMode::Fragment,
+        }
+    }
+}
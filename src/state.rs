@@ -1,130 +1,398 @@
 use crate::{
-    camera::{CameraController, CameraResource},
+    benchmark::{Benchmark, BenchmarkReport},
+    camera::CameraController,
+    camera_path::{CameraPath, CameraPathRecorder},
+    config::Config,
+    debug_draw::DebugDraw,
+    entity::{Entity, MeshId},
     gpu_context::{GpuContext, WindowSurface},
+    mesh::MeshData,
     player::Player,
     player_controller::PlayerController,
-    world::{World, WorldResource},
+    renderer::Renderer,
+    screenshot,
+    voxel::Voxel,
+    voxel_object::{VoxelModel, VoxelModelId, VoxelObject},
+    world::World,
+    world_position::WorldPosition,
+};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use winit::{
+    event::{ElementState, MouseButton},
+    keyboard::KeyCode,
+    window::Window,
 };
-use std::sync::Arc;
-use winit::window::Window;
 
 pub struct State {
     pub display: WindowSurface,
-    render_pipeline: wgpu::RenderPipeline,
+    renderer: Renderer,
     gpu: GpuContext,
 
     // Player
-    pub player_controller: PlayerController,
+    player_controller: PlayerController,
     player: Player,
 
     // Camera
-    camera_resource: CameraResource,
     pub camera_controller: CameraController,
+    recorder: Option<CameraPathRecorder>,
+    benchmark: Option<Benchmark>,
+
+    // Screenshots
+    screenshot_scale: u32,
+    pending_screenshot: Option<u32>, // Resolution multiplier of the screenshot to take after the frame
+
+    // Debug
+    debug_draw: DebugDraw,
+    show_chunk_grid: bool,
+    svo_overlay_level: Option<u32>, // Octree depth whose nodes are drawn
+    pinned_gizmo: Option<PlayerGizmo>, // Player hitbox & pick ray, left in place to look at them from outside
+    heatmap: bool,
+
+    // Entities
+    entities: Vec<Entity>,
+    spawn_mesh: MeshId,
+    voxel_objects: Vec<VoxelObject>,
+    voxel_models: Vec<VoxelModelId>, // Spawned in turn
 
     // World
     world: World,
-    world_resource: WorldResource,
+    place_glass: bool, // Else stone, with the right click
 }
 
+struct PlayerGizmo {
+    hitbox: (WorldPosition, WorldPosition),
+    eye: WorldPosition,
+    front: glam::Vec3,
+}
+
+const SVO_OVERLAY_RADIUS: i32 = 64; // Only the nodes around the player, deep levels have a lot of them
+const PICK_DISTANCE: f32 = 32.0;
+const SPAWN_DISTANCE: f32 = 8.0;
+const EDIT_DISTANCE: f32 = 16.0;
+const GLASS: glam::Vec3 = glam::Vec3::new(0.8, 0.9, 0.95);
+const GLASS_ALPHA: f32 = 0.15;
+const GENERATION_BUDGET: Duration = Duration::from_millis(4); // Per frame, to integrate generated chunks
+
 impl State {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
-        let (gpu, display) = GpuContext::new(window).await?;
+    pub async fn new(window: Arc<Window>, config: &Config) -> anyhow::Result<Self> {
+        let recorder = config
+            .record_path
+            .as_deref()
+            .map(CameraPathRecorder::create)
+            .transpose()?;
+        let benchmark = config
+            .play_path
+            .as_deref()
+            .map(CameraPath::load)
+            .transpose()?
+            .map(Benchmark::new);
+
+        let (gpu, display) = GpuContext::new(window, benchmark.is_none()).await?;
+
+        // Benchmarks need the whole world from the first frame
+        let world = World::from_config(config, benchmark.is_none())?;
 
-        let player = Player::new(glam::Vec3::ZERO);
+        let player = Player::new(world.params.spawn_position(), config.camera);
         let player_controller = PlayerController::default();
-        let camera_resource = CameraResource::new(&gpu.device, &player.camera);
         let camera_controller = CameraController::new(0.1);
+        let mut renderer = Renderer::new(
+            &gpu.device,
+            &gpu.queue,
+            display.config.format,
+            glam::uvec2(display.config.width, display.config.height),
+            &config.render,
+            &player.camera,
+            &world,
+        );
 
-        let world = World::new();
-        let mut world_resource = WorldResource::new(&gpu.device, world.params.view_distance);
-
-        // Confiure the render pipeline
-        let shader = gpu
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let render_pipeline_layout =
-            gpu.device
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("Render Pipeline Layout"),
-                    bind_group_layouts: &[
-                        &camera_resource.layout, // @group(0)
-                        &world_resource.layout,  // @group(1)
-                    ],
-                    immediate_size: 0,
-                });
-        let render_pipeline = gpu
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    buffers: &[],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    // 3.
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        // 4.
-                        format: display.config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview_mask: None,
-                cache: None,
-            });
+        let mesh = match &config.mesh_path {
+            Some(path) => MeshData::load_obj(path)?,
+            None => MeshData::cube(glam::vec3(0.9, 0.4, 0.1)),
+        };
+        let spawn_mesh = renderer.add_mesh(&gpu.device, &mesh);
 
-        world_resource.upload(&gpu.queue, &world);
+        let voxel_models = match &config.vox_path {
+            Some(path) => VoxelModel::load(path)?
+                .iter()
+                .map(|model| renderer.add_voxel_model(&gpu.queue, model))
+                .collect::<anyhow::Result<_>>()?,
+            None => Vec::new(),
+        };
 
         Ok(Self {
             gpu,
             display,
-            render_pipeline,
+            renderer,
             player_controller,
             player,
-            camera_resource,
             camera_controller,
+            recorder,
+            benchmark,
+            screenshot_scale: config.screenshot_scale,
+            pending_screenshot: None,
+            debug_draw: DebugDraw::default(),
+            show_chunk_grid: false,
+            svo_overlay_level: None,
+            pinned_gizmo: None,
+            heatmap: false,
+            entities: Vec::new(),
+            spawn_mesh,
+            voxel_objects: Vec::new(),
+            voxel_models,
             world,
-            world_resource,
+            place_glass: false,
         })
     }
 
-    pub fn update(&mut self, dt: std::time::Duration) {
+    pub fn update(&mut self, dt: Duration) {
         let size = glam::USizeVec2::new(
             self.display.config.width as usize,
             self.display.config.height as usize,
         );
 
-        self.camera_controller
-            .update_camera(&mut self.player.camera, size.x, size.y);
-        self.player.move_player(&self.player_controller, dt, 10.0);
-        self.camera_resource
-            .update(&self.gpu.queue, &self.player.camera);
+        self.renderer.update_frame_time(&self.gpu.queue, dt);
+
+        if let Some(benchmark) = &mut self.benchmark {
+            // Scripted flythrough, the inputs are ignored
+            benchmark.record_frame(dt);
+            if let Some(pose) = benchmark.next_pose() {
+                self.player.apply_pose(&pose);
+            }
+            self.camera_controller.reset();
+            self.player.camera.update(size.x, size.y);
+        } else {
+            self.camera_controller
+                .update_camera(&mut self.player.camera, size.x, size.y);
+            self.player.move_player(&self.player_controller, dt, 10.0);
+        }
+
+        if let Some(recorder) = &mut self.recorder
+            && let Err(e) = recorder.record(dt, &self.player.camera)
+        {
+            log::error!("Camera path recording stopped: {e}");
+            self.recorder = None;
+        }
+
+        self.player.camera.medium = self.world.medium_at(self.player.camera.position);
+        self.renderer
+            .update_camera(&self.gpu.queue, &self.player.camera);
+
+        // Also moves the GPU chunk grid with the world one
+        let changed = self
+            .world
+            .update_generation(self.player.camera.position.chunk);
+        self.renderer
+            .update_chunks(&self.gpu.queue, &self.world, &changed);
+        let start = Instant::now();
+        while start.elapsed() < GENERATION_BUDGET
+            && let Some(chunk_coord) = self.world.integrate_generated_chunk()
+        {
+            self.renderer
+                .update_chunks(&self.gpu.queue, &self.world, &[chunk_coord]);
+        }
+
+        let progress = self
+            .loading_progress()
+            .map(|(generated, requested)| generated as f32 / requested as f32);
+        self.renderer
+            .set_loading_progress(&self.gpu.queue, progress);
+
+        let edited = self.world.rebuild_dirty_chunks();
+        if !edited.is_empty() {
+            self.renderer
+                .update_chunks(&self.gpu.queue, &self.world, &edited);
+        }
+
+        self.renderer
+            .update_entities(&self.gpu.device, &self.gpu.queue, &self.entities);
+        self.renderer
+            .update_voxel_objects(&self.gpu.queue, &self.voxel_objects);
+
+        self.debug_draw.clear(self.player.camera.render_origin());
+        self.draw_debug_overlays();
+        self.renderer
+            .update_debug_draw(&self.gpu.device, &self.gpu.queue, &self.debug_draw);
+    }
+
+    fn draw_debug_overlays(&mut self) {
+        if self.show_chunk_grid {
+            for chunk_coord in self.world.chunk_coords() {
+                let min = WorldPosition::new(chunk_coord, glam::Vec3::ZERO);
+                let max = WorldPosition::new(chunk_coord + 1, glam::Vec3::ZERO);
+                self.debug_draw
+                    .aabb(min, max, glam::vec4(1.0, 1.0, 0.0, 1.0));
+            }
+        }
+
+        if let Some(level) = self.svo_overlay_level {
+            let center = self.player.camera.position.voxel();
+            let near_player = |min: glam::IVec3, size: i32| {
+                let closest = center.clamp(min, min + size);
+                (closest - center).abs().max_element() <= SVO_OVERLAY_RADIUS
+            };
+
+            let boxes = self.world.level_boxes(level, near_player);
+            for (min, size) in boxes {
+                let max = WorldPosition::from_voxel(min + size);
+                self.debug_draw.aabb(
+                    WorldPosition::from_voxel(min),
+                    max,
+                    glam::vec4(0.0, 1.0, 1.0, 0.5),
+                );
+            }
+        }
+
+        if let Some(gizmo) = &self.pinned_gizmo {
+            let (min, max) = gizmo.hitbox;
+            let target = gizmo.eye + gizmo.front * PICK_DISTANCE;
+            self.debug_draw
+                .aabb(min, max, glam::vec4(0.0, 1.0, 0.0, 1.0));
+            self.debug_draw
+                .line(gizmo.eye, target, glam::vec4(1.0, 0.0, 0.0, 1.0));
+            self.debug_draw
+                .sphere(target, 0.5, glam::vec4(1.0, 0.0, 0.0, 1.0));
+        }
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.renderer.scale()
+    }
+
+    // (generated, requested) chunks while the terrain around the player is being generated
+    pub fn loading_progress(&self) -> Option<(usize, usize)> {
+        self.world.generation_progress()
+    }
+
+    // Some(report) once the camera path playback is over
+    pub fn benchmark_report(&self) -> Option<BenchmarkReport> {
+        self.benchmark
+            .as_ref()
+            .filter(|b| b.is_finished())
+            .map(Benchmark::report)
+    }
+
+    pub fn process_keyboard(&mut self, key: KeyCode, key_state: ElementState) {
+        self.player_controller.process_keyboard(key, key_state);
+
+        if key_state != ElementState::Pressed {
+            return;
+        }
+
+        match key {
+            KeyCode::KeyP => self.player.camera.toggle_projection(),
+            KeyCode::F2 => self.pending_screenshot = Some(1),
+            KeyCode::F3 => self.pending_screenshot = Some(self.screenshot_scale),
+            KeyCode::F4 => self.show_chunk_grid = !self.show_chunk_grid,
+            KeyCode::F5 => self.cycle_svo_overlay(),
+            KeyCode::F6 => self.toggle_pinned_gizmo(),
+            KeyCode::F7 => self.spawn_entity(),
+            KeyCode::F8 => self.spawn_voxel_object(),
+            KeyCode::KeyG => {
+                self.place_glass = !self.place_glass;
+                log::info!(
+                    "Placing {}",
+                    if self.place_glass { "glass" } else { "stone" }
+                );
+            }
+            KeyCode::F9 => {
+                self.heatmap = !self.heatmap;
+                self.renderer.set_heatmap(&self.gpu.queue, self.heatmap);
+            }
+            _ => (),
+        }
+    }
+
+    // Left click breaks the voxel under the crosshair, right click places one against it
+    pub fn process_mouse_button(&mut self, button: MouseButton, button_state: ElementState) {
+        if button_state != ElementState::Pressed || self.benchmark.is_some() {
+            return;
+        }
+        let camera = &self.player.camera;
+        let Some((hit, normal)) = self
+            .world
+            .raycast(camera.position, camera.front, EDIT_DISTANCE)
+        else {
+            return;
+        };
+
+        match button {
+            MouseButton::Left => self.world.set_voxel(hit, Voxel::default()),
+            MouseButton::Right if normal != glam::IVec3::ZERO => {
+                let position = hit + normal;
+                // Not inside the player
+                if self.player.overlaps_voxel(position) {
+                    return;
+                }
+                let voxel = match self.place_glass {
+                    true => Voxel::transparent(GLASS, GLASS_ALPHA),
+                    false => Voxel::new(glam::vec3(0.6, 0.6, 0.6), true),
+                };
+                self.world.set_voxel(position, voxel);
+            }
+            _ => (),
+        }
+    }
+
+    // Off -> level 0 (root) -> ... -> leaves -> off
+    fn cycle_svo_overlay(&mut self) {
+        self.svo_overlay_level = match self.svo_overlay_level {
+            None => Some(0),
+            Some(level) if level < self.world.params.max_depth() => Some(level + 1),
+            Some(_) => None,
+        };
+        match self.svo_overlay_level {
+            Some(level) => log::info!("SVO overlay: level {level}"),
+            None => log::info!("SVO overlay: off"),
+        }
+    }
+
+    fn toggle_pinned_gizmo(&mut self) {
+        self.pinned_gizmo = match self.pinned_gizmo {
+            Some(_) => None,
+            None => Some(PlayerGizmo {
+                hitbox: self.player.hitbox(),
+                eye: self.player.camera.position,
+                front: self.player.camera.front,
+            }),
+        };
+    }
+
+    // In front of the player, facing it
+    fn spawn_entity(&mut self) {
+        let camera = &self.player.camera;
+        let mut entity = Entity::new(
+            self.spawn_mesh,
+            camera.position + camera.front * SPAWN_DISTANCE,
+        );
+        entity.rotation = glam::Quat::from_rotation_y(-camera.yaw().to_radians());
+        self.entities.push(entity);
+    }
+
+    fn spawn_voxel_object(&mut self) {
+        if self.voxel_models.is_empty() {
+            log::warn!("No voxel model to spawn, load a .vox file with --vox");
+            return;
+        }
+
+        let camera = &self.player.camera;
+        let model = self.voxel_models[self.voxel_objects.len() % self.voxel_models.len()];
+        let mut object = VoxelObject::new(model, camera.position + camera.front * SPAWN_DISTANCE);
+        object.rotation = glam::Quat::from_rotation_y(-camera.yaw().to_radians());
+        self.voxel_objects.push(object);
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.display.resize(&self.gpu.device, width, height);
+            self.renderer.resize(
+                &self.gpu.device,
+                &self.gpu.queue,
+                glam::uvec2(width, height),
+            );
         }
     }
 
@@ -141,38 +409,41 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    depth_slice: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-                multiview_mask: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_resource.bind_group, &[]);
-            render_pass.set_bind_group(1, &self.world_resource.bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
-        }
+        self.renderer
+            .render(&mut encoder, &view, &self.display.depth_view);
 
         self.gpu.queue.submit(std::iter::once(encoder.finish()));
         output.present();
 
+        if let Some(scale) = self.pending_screenshot.take() {
+            match self.save_screenshot(scale) {
+                Ok(path) => println!("Screenshot saved to {}", path.display()),
+                Err(e) => eprintln!("Screenshot failed: {e:?}"),
+            }
+        }
+
         Ok(())
     }
+
+    fn save_screenshot(&self, scale: u32) -> anyhow::Result<PathBuf> {
+        // Same aspect ratio as the window, so the camera matrices stay valid
+        let window_size = glam::uvec2(self.display.config.width, self.display.config.height);
+        let max_dimension = self.gpu.device.limits().max_texture_dimension_2d;
+        let scale = (scale as f32).min(max_dimension as f32 / window_size.max_element() as f32);
+        let size = (window_size.as_vec2() * scale)
+            .as_uvec2()
+            .max(glam::UVec2::ONE);
+
+        let pixels = screenshot::capture(
+            &self.gpu.device,
+            &self.gpu.queue,
+            &self.renderer,
+            self.display.config.format,
+            size,
+        )?;
+
+        let path = screenshot::timestamped_path()?;
+        screenshot::save_png(&path, size, &pixels)?;
+        Ok(path)
+    }
 }
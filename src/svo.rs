@@ -1,38 +1,121 @@
-use std::u32;
+use rayon::prelude::*;
 
 #[repr(C)]
 #[derive(Default, Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct SVONode {
-    children_idx: u32, // 0 if empty
+pub struct SvoNode {
+    children_idx: u32, // 0 if empty, u32::MAX for a leaf, else relative to the root
     color: u32,        // Packed color, for LOD
 }
 
+// Node order of the bottom-up builder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeLayout {
+    BreadthFirst, // Level by level, the top of the tree stays together
+    DepthFirst,   // Each subtree is contiguous, the children follow their parent
+}
+
 #[derive(Default)]
-pub struct SVO {
-    pub nodes: Vec<SVONode>,
+pub struct Svo {
+    pub nodes: Vec<SvoNode>,
 }
 
-impl SVO {
+impl Svo {
     pub fn new() -> Self {
         let mut nodes = Vec::with_capacity(1024); // Reserved capacity -> 1kB
-        nodes.push(SVONode::default()); // The root node
+        nodes.push(SvoNode::default()); // The root node
 
         Self { nodes }
     }
 
+    // Bottom-up build from the voxels sorted by Morton code, a duplicated position keeps one of its
+    // colors. Coordinates in [0, 2^max_depth) like `insert`
+    pub fn from_morton(
+        voxels: Vec<(glam::IVec3, u32)>,
+        max_depth: u32,
+        layout: NodeLayout,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            max_depth <= MORTON_BITS,
+            "Octree depth {max_depth} is above the {MORTON_BITS} levels of the Morton codes"
+        );
+        let size = 2_i32.pow(max_depth);
+        if let Some((coord, _)) = voxels
+            .iter()
+            .find(|(coord, _)| coord.min_element() < 0 || coord.max_element() >= size)
+        {
+            anyhow::bail!("Voxel {coord} is outside of the octree [0, {size})");
+        }
+
+        let mut svo = Self::new();
+        let levels = MortonLevels::new(voxels, max_depth);
+        if levels.colors.is_empty() {
+            return Ok(svo);
+        }
+
+        match layout {
+            NodeLayout::BreadthFirst => {
+                let mut node_indices = vec![0]; // Of the current level nodes in `svo.nodes`
+                for depth in 0..max_depth as usize {
+                    let mut next = Vec::with_capacity(levels.codes[depth + 1].len());
+                    for (i, &node_idx) in node_indices.iter().enumerate() {
+                        let children_idx = svo.allocate_children();
+                        svo.nodes[node_idx].children_idx = children_idx;
+                        for child in levels.children(depth, i) {
+                            next.push(
+                                (children_idx + levels.child_offset(depth + 1, child)) as usize,
+                            );
+                        }
+                    }
+                    node_indices = next;
+                }
+
+                for (node_idx, color) in node_indices.into_iter().zip(&levels.colors) {
+                    svo.set_leaf(node_idx, *color);
+                }
+            }
+            NodeLayout::DepthFirst => svo.emit_depth_first(&levels, 0, 0, 0),
+        }
+
+        Ok(svo)
+    }
+
+    fn emit_depth_first(&mut self, levels: &MortonLevels, depth: usize, i: usize, node_idx: usize) {
+        if depth == levels.codes.len() - 1 {
+            self.set_leaf(node_idx, levels.colors[i]);
+            return;
+        }
+
+        let children_idx = self.allocate_children();
+        self.nodes[node_idx].children_idx = children_idx;
+        for child in levels.children(depth, i) {
+            let child_idx = children_idx + levels.child_offset(depth + 1, child);
+            self.emit_depth_first(levels, depth + 1, child, child_idx as usize);
+        }
+    }
+
+    fn set_leaf(&mut self, node_idx: usize, color: u32) {
+        self.nodes[node_idx].children_idx = u32::MAX;
+        self.nodes[node_idx].color = color;
+    }
+
     pub fn allocate_children(&mut self) -> u32 {
         let new_idx = self.nodes.len() as u32;
 
         for _ in 0..8 {
-            self.nodes.push(SVONode::default());
+            self.nodes.push(SvoNode::default());
         }
 
         new_idx
     }
 
-    pub fn insert(&mut self, coord: glam::IVec3, color: u32, max_depth: u32) {
+    // Coordinates in [0, 2^max_depth), anything else would land in the wrong node
+    pub fn insert(&mut self, coord: glam::IVec3, color: u32, max_depth: u32) -> anyhow::Result<()> {
         let mut current_node_idx = 0; // Start in the root
         let mut size = 2_i32.pow(max_depth); // World size in block
+        anyhow::ensure!(
+            coord.min_element() >= 0 && coord.max_element() < size,
+            "Voxel {coord} is outside of the octree [0, {size})"
+        );
 
         // Work coordinates (begin to world coordinates)
         let mut cur_coord = coord;
@@ -69,15 +152,216 @@ impl SVO {
         }
 
         // In the leaf, set the color
-        self.nodes[current_node_idx].children_idx = u32::MAX;
-        self.nodes[current_node_idx].color = color;
+        self.set_leaf(current_node_idx, color);
+        Ok(())
+    }
+
+    // Boxes (min corner, size) of the non empty nodes at `level`, the subtrees rejected by `visit` are skipped
+    pub fn level_boxes(
+        &self,
+        level: u32,
+        max_depth: u32,
+        visit: impl Fn(glam::IVec3, i32) -> bool,
+    ) -> Vec<(glam::IVec3, i32)> {
+        let mut boxes = Vec::new();
+        let mut stack = vec![(0_u32, glam::IVec3::ZERO, 2_i32.pow(max_depth), 0_u32)];
+
+        while let Some((node_idx, min, size, depth)) = stack.pop() {
+            let children_idx = self.nodes[node_idx as usize].children_idx;
+            if children_idx == 0 || !visit(min, size) {
+                continue;
+            }
+
+            if depth == level {
+                boxes.push((min, size));
+                continue;
+            }
+            if children_idx == u32::MAX {
+                continue; // Leaf above the level
+            }
+
+            let half = size / 2;
+            for child_offset in 0..8 {
+                let offset = glam::IVec3::new(
+                    (child_offset >> 2) & 1,
+                    (child_offset >> 1) & 1,
+                    child_offset & 1,
+                );
+                stack.push((
+                    children_idx + child_offset as u32,
+                    min + offset * half,
+                    half,
+                    depth + 1,
+                ));
+            }
+        }
+
+        boxes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes[0].children_idx == 0
     }
 
     pub fn size(&self) -> usize {
-        self.nodes.len() * size_of::<SVONode>()
+        self.nodes.len() * size_of::<SvoNode>()
     }
+}
+
+// Sorted codes of the non empty nodes of each level, from the root to the leaves
+struct MortonLevels {
+    codes: Vec<Vec<u64>>,
+    first_children: Vec<Vec<usize>>, // Children of node i in the next level: [first[i], first[i + 1])
+    colors: Vec<u32>,                // Of the leaves
+}
+
+impl MortonLevels {
+    fn new(voxels: Vec<(glam::IVec3, u32)>, max_depth: u32) -> Self {
+        let mut leaves: Vec<(u64, u32)> = voxels
+            .into_iter()
+            .map(|(coord, color)| (morton_code(coord.as_uvec3()), color))
+            .collect();
+        leaves.par_sort_unstable_by_key(|(code, _)| *code);
+        leaves.dedup_by_key(|(code, _)| *code);
 
-    pub fn as_bytes(&self) -> &[u8] {
-        return bytemuck::cast_slice(&self.nodes);
+        let (leaf_codes, colors): (Vec<_>, Vec<_>) = leaves.into_iter().unzip();
+        let mut codes = vec![leaf_codes];
+        let mut first_children = Vec::new();
+        for _ in 0..max_depth {
+            // Parents are the codes without their last child offset
+            let children = codes.last().unwrap();
+            let mut parents: Vec<u64> = Vec::new();
+            let mut first = Vec::new();
+            for (i, code) in children.iter().enumerate() {
+                if parents.last() != Some(&(code >> 3)) {
+                    parents.push(code >> 3);
+                    first.push(i);
+                }
+            }
+            first.push(children.len());
+
+            codes.push(parents);
+            first_children.push(first);
+        }
+        codes.reverse();
+        first_children.reverse();
+
+        Self {
+            codes,
+            first_children,
+            colors,
+        }
+    }
+
+    fn children(&self, depth: usize, i: usize) -> std::ops::Range<usize> {
+        self.first_children[depth][i]..self.first_children[depth][i + 1]
+    }
+
+    // Position in its parent children (0-7)
+    fn child_offset(&self, depth: usize, i: usize) -> u32 {
+        (self.codes[depth][i] & 7) as u32
+    }
+}
+
+const MORTON_BITS: u32 = 21; // Per axis, 63 bits in total
+
+// Interleaved bits, each level gives a child offset (x << 2) | (y << 1) | z
+fn morton_code(coord: glam::UVec3) -> u64 {
+    let mut code = 0;
+    for bit in 0..MORTON_BITS {
+        let x = (coord.x as u64 >> bit) & 1;
+        let y = (coord.y as u64 >> bit) & 1;
+        let z = (coord.z as u64 >> bit) & 1;
+        code |= ((x << 2) | (y << 1) | z) << (bit * 3);
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_bytes(svo: &Svo) -> Vec<u8> {
+        bytemuck::cast_slice(&svo.nodes).to_vec()
+    }
+
+    // Leaf color at `coord`, None in the empty nodes
+    fn get(svo: &Svo, coord: glam::IVec3, max_depth: u32) -> Option<u32> {
+        let mut node_idx = 0;
+        for level in (0..max_depth).rev() {
+            let children_idx = svo.nodes[node_idx].children_idx;
+            if children_idx == 0 {
+                return None;
+            }
+            let bit = |axis: i32| ((axis >> level) & 1) as u32;
+            node_idx =
+                (children_idx + (bit(coord.x) << 2 | bit(coord.y) << 1 | bit(coord.z))) as usize;
+        }
+        let leaf = svo.nodes[node_idx];
+        (leaf.children_idx == u32::MAX).then_some(leaf.color)
+    }
+
+    #[test]
+    fn insert_rejects_the_voxels_outside_of_the_tree() {
+        let mut svo = Svo::new();
+        svo.insert(glam::ivec3(1, 2, 3), 0xFF0000FF, 3).unwrap();
+        let before = node_bytes(&svo);
+
+        for coord in [
+            glam::ivec3(-1, 0, 0),
+            glam::ivec3(0, -8, 0),
+            glam::ivec3(0, 0, 8),
+            glam::ivec3(8, 8, 8),
+            glam::IVec3::MIN,
+        ] {
+            assert!(svo.insert(coord, 0xFF00FF00, 3).is_err(), "{coord}");
+            assert_eq!(node_bytes(&svo), before, "{coord}");
+        }
+    }
+
+    #[test]
+    fn from_morton_matches_insert() {
+        const DEPTH: u32 = 4;
+        let size = 2_i32.pow(DEPTH);
+        let voxels: Vec<_> = (0..size.pow(3))
+            .filter(|i| i % 5 == 0)
+            .map(|i| {
+                let coord = glam::ivec3(i % size, i / size % size, i / size / size);
+                (coord, 0xFF000000 | i as u32)
+            })
+            .collect();
+
+        let mut inserted = Svo::new();
+        for &(coord, color) in &voxels {
+            inserted.insert(coord, color, DEPTH).unwrap();
+        }
+        assert_eq!(get(&inserted, voxels[1].0, DEPTH), Some(voxels[1].1));
+        for layout in [NodeLayout::BreadthFirst, NodeLayout::DepthFirst] {
+            let built = Svo::from_morton(voxels.clone(), DEPTH, layout).unwrap();
+            assert_eq!(built.nodes.len(), inserted.nodes.len());
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        let coord = glam::ivec3(x, y, z);
+                        assert_eq!(
+                            get(&built, coord, DEPTH),
+                            get(&inserted, coord, DEPTH),
+                            "{layout:?} {coord}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_morton_rejects_the_voxels_outside_of_the_tree() {
+        let color = 0xFF0000FF;
+        for coord in [glam::ivec3(-1, 0, 0), glam::ivec3(0, 8, 0)] {
+            let voxels = vec![(glam::IVec3::ZERO, color), (coord, color)];
+            assert!(Svo::from_morton(voxels, 3, NodeLayout::BreadthFirst).is_err());
+        }
+        let voxels = vec![(glam::IVec3::ZERO, color)];
+        assert!(Svo::from_morton(voxels, MORTON_BITS + 1, NodeLayout::DepthFirst).is_err());
     }
 }
@@ -0,0 +1,494 @@
+// Shared by the fragment (shader.wgsl) & compute (raymarch_compute.wgsl) raymarching paths,
+// each of them must define `fn fetch_node(idx: u32) -> SVONode`
+
+// ===========================
+// Structures
+// ===========================
+
+struct CameraUniform {
+    inv_view_proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    origin: vec3<f32>,
+    is_orthographic: u32,
+    medium: u32, // Packed color of the transparent voxel the camera is in, 0 (air) outside
+};
+
+struct WorldUniforms {
+    grid_size: u32,
+    object_count: u32,
+    heatmap: u32,  // Debug view, iteration count instead of the voxel colors
+    brickmap: u32, // Chunk structure, brickmaps instead of octrees
+    grid_origin: vec3<i32>, // Chunk in the first grid cell
+};
+
+// Dynamic voxel model, traced in its own octree
+struct VoxelObject {
+    inv_model: mat4x4<f32>, // World -> model space
+    aabb_min: vec3<f32>,    // World space bounds
+    root: u32,              // Root node in object_svo
+    aabb_max: vec3<f32>,
+    size: f32,              // Root box size in model space
+};
+
+struct Ray {
+    origin: vec3<f32>,
+    dir: vec3<f32>,
+};
+
+struct RayHit {
+    color: vec4<f32>, // Alpha below 1 for the transparent voxels, the opacity of 1 voxel of thickness
+    t: f32,           // Distance along the ray, MISS if nothing was hit
+    t_exit: f32,      // Where the ray leaves the hit leaf
+};
+
+struct BoundingBox {
+    min: vec3<f32>,   // Down left corner of the box
+    size: f32,
+}
+
+struct SVONode {
+    children_idx: u32,
+    color: u32,
+}
+
+struct StackNode {
+    node_idx: u32,
+    box: BoundingBox,
+}
+
+// Walk through a grid of cells, from the one the ray enters to the next ones it crosses
+struct Dda {
+    cell: vec3<i32>,
+    step: vec3<i32>,
+    t: f32,              // Distance along the ray where it enters the cell
+    t_max: vec3<f32>,    // To the next cell on each axis
+    t_delta: vec3<f32>,  // Between 2 cells on each axis
+}
+
+// DDA through the chunk grid, kept between the transparent hits of a ray
+struct ChunkMarch {
+    dda: Dda,
+    grid_min: vec3<f32>,
+    done: bool, // Out of the grid
+}
+
+// ===========================
+// Constants
+// ===========================
+
+const EPSILON: f32 = 0.00001;
+const MISS: f32 = -1.0;
+const MAX_TRANSPARENT_HITS: u32 = 1024; // Only a guard, the transmittance ends the loop long before
+const MIN_TRANSMITTANCE: f32 = 0.01; // Below it, what's behind the transparent voxels is invisible
+const EXIT_BIAS: f32 = 0.001;        // Past the exit of a transparent leaf, so it isn't hit again
+const CHUNK_SIZE: u32 = 16;
+const EMPTY_CHUNK: u32 = 0xFFFFFFFFu;
+const BRICK_SIZE: i32 = 8;
+const OCCUPANCY_WORDS: u32 = 16; // 1 bit per voxel of a brick
+const WORLD_POOL: u32 = 0u;
+const OBJECT_POOL: u32 = 1u;
+
+// ===========================
+// Bindings
+// ===========================
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> svo: array<SVONode>;
+@group(1) @binding(1)
+var<uniform> world_params: WorldUniforms;
+@group(1) @binding(2)
+var<storage, read> object_svo: array<SVONode>;
+@group(1) @binding(3)
+var<storage, read> objects: array<VoxelObject>;
+@group(1) @binding(4)
+var<storage, read> chunk_grid: array<u32>; // Root of each chunk structure in svo, x + y * size + z * size²
+
+// ===========================
+// Utility functions
+// ===========================
+
+fn get_env_color(ray: Ray) -> vec3<f32> {
+    let sky_gradient = ray.dir.y + 1;
+    return mix(vec3<f32>(1.0), vec3<f32>(0.5, 0.7, 1.0), sky_gradient);
+}
+
+// Map a NDC point (depth 0 = near plane, 1 = far plane) back to world space
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let world = camera.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+fn intersect_aabb(ray: Ray, box: BoundingBox) -> vec2<f32> {
+    return intersect_bounds(ray, box.min, box.min + box.size);
+}
+
+fn intersect_bounds(ray: Ray, bounds_min: vec3<f32>, bounds_max: vec3<f32>) -> vec2<f32> {
+    let t0 = (bounds_min - ray.origin) / ray.dir;
+    let t1 = (bounds_max - ray.origin) / ray.dir;
+
+    // If negative direction, t0 > t1
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+
+    // In the box if we are in all 3 axis
+    let t_enter = max(t_min.x, max(t_min.y, t_min.z));
+    let t_exit = min(t_max.x, min(t_max.y, t_max.z));
+
+    return vec2<f32>(t_enter, t_exit);
+}
+
+fn child_box(parent: BoundingBox, child_idx: u32) -> BoundingBox {
+    let half = parent.size / 2.0;
+    let center = parent.min + half;
+
+    var b: BoundingBox;
+    b.min.x = select(parent.min.x, center.x, bool(child_idx & 4u));
+    b.min.y = select(parent.min.y, center.y, bool(child_idx & 2u));
+    b.min.z = select(parent.min.z, center.z, bool(child_idx & 1u));
+    b.size = half;
+    return b;
+}
+
+// Return an array of children indices, sorted by ray intersection
+fn sort_children(ray: Ray) -> array<u32, 8> {
+    var mask = 0u;
+    if (ray.dir.x < 0.0) { mask |= 4u; } // 0b100
+    if (ray.dir.y < 0.0) { mask |= 2u; } // 0b010
+    if (ray.dir.z < 0.0) { mask |= 1u; } // 0b001
+
+    return array<u32, 8>(
+        0u ^ mask,
+        1u ^ mask,
+        2u ^ mask,
+        3u ^ mask,
+        4u ^ mask,
+        5u ^ mask,
+        6u ^ mask,
+        7u ^ mask,
+    );
+}
+
+// Primary ray of a pixel, from its NDC coordinates
+fn camera_ray(ndc: vec2<f32>) -> Ray {
+    let target_pos = unproject(ndc, 1.0);
+
+    var ray: Ray;
+    if (camera.is_orthographic != 0u) {
+        // Parallel rays, each pixel starts from its own point on the near plane
+        ray.origin = unproject(ndc, 0.0);
+    } else {
+        ray.origin = camera.origin;
+    }
+    ray.dir = normalize(target_pos - ray.origin);
+    return ray;
+}
+
+fn dda_start(ray: Ray, grid_min: vec3<f32>, cell_size: f32, cells: i32, t_enter: f32) -> Dda {
+    let start = ray.origin + ray.dir * t_enter;
+
+    var dda: Dda;
+    dda.cell = clamp(vec3<i32>(floor((start - grid_min) / cell_size)), vec3<i32>(0), vec3<i32>(cells - 1));
+    dda.step = vec3<i32>(sign(ray.dir));
+    dda.t = t_enter;
+
+    // Never for the axes the ray is parallel to
+    let parallel = ray.dir == vec3<f32>(0.0);
+    let next_boundary = grid_min + vec3<f32>(dda.cell + max(dda.step, vec3<i32>(0))) * cell_size;
+    dda.t_max = select((next_boundary - ray.origin) / ray.dir, vec3<f32>(1e30), parallel);
+    dda.t_delta = select(abs(cell_size / ray.dir), vec3<f32>(1e30), parallel);
+    return dda;
+}
+
+// Move to the closest next cell, false once out of the grid
+fn dda_step(dda: ptr<function, Dda>, cells: i32) -> bool {
+    let t_max = (*dda).t_max;
+    var axis = 2;
+    if (t_max.x < t_max.y && t_max.x < t_max.z) {
+        axis = 0;
+    } else if (t_max.y < t_max.z) {
+        axis = 1;
+    }
+
+    (*dda).t = t_max[axis];
+    (*dda).cell[axis] += (*dda).step[axis];
+    (*dda).t_max[axis] += (*dda).t_delta[axis];
+    return all((*dda).cell >= vec3<i32>(0)) && all((*dda).cell < vec3<i32>(cells));
+}
+
+fn leaf_hit(color: u32, t: f32, t_exit: f32, iteration: u32) -> RayHit {
+    if (world_params.heatmap != 0u) {
+        return RayHit(vec4<f32>(vec3<f32>(1.0 / f32(iteration + 1)), 1.0), t, t_exit);
+    }
+    return RayHit(unpack4x8unorm(color), t, t_exit);
+}
+
+// NDC depth of a hit, using the camera projection (far plane if nothing was hit)
+fn hit_depth(ray: Ray, t: f32) -> f32 {
+    if (t < 0.0) {
+        return 1.0;
+    }
+
+    let clip = camera.view_proj * vec4<f32>(ray.origin + ray.dir * t, 1.0);
+    return clamp(clip.z / clip.w, 0.0, 1.0);
+}
+
+// ===========================
+// Octree traversal
+// ===========================
+
+fn fetch_pool_node(pool: u32, idx: u32) -> SVONode {
+    if (pool == OBJECT_POOL) {
+        return object_svo[idx];
+    }
+    return fetch_node(idx);
+}
+
+// Blend the transparent leaves front to back, until an opaque leaf, a miss or too little light
+// goes through. A leaf's opacity depends on the distance traveled through it, so a volume of
+// transparent voxels is as opaque as it is thick. The chunk DDA goes on past each transparent leaf,
+// only the structure of its chunk is traversed again. The hit is the first opaque one
+fn trace(ray: Ray) -> RayHit {
+    var march = chunk_march_start(ray);
+    var chunk_hit = next_chunk_hit(ray, &march, 0.0);
+    var hit = closest_object_hit(ray, 0.0, chunk_hit);
+    var color = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    // Skipped like World::raycast does, it's fogged over the distance the ray spends in it instead
+    let medium = unpack4x8unorm(camera.medium);
+    var medium_distance = 0.0;
+
+    for (var i = 0u; i < MAX_TRANSPARENT_HITS && hit.t != MISS && hit.color.a < 1.0; i++) {
+        if (camera.medium != 0u && all(hit.color == medium)) {
+            medium_distance += hit.t_exit - hit.t;
+        } else {
+            let opacity = 1.0 - pow(1.0 - hit.color.a, hit.t_exit - hit.t);
+            color += transmittance * opacity * hit.color.rgb;
+            transmittance *= 1.0 - opacity;
+            if (transmittance < MIN_TRANSMITTANCE) {
+                hit.color = vec4<f32>(0.0); // Already blended, & what's behind it doesn't show
+                break;
+            }
+        }
+
+        let t_min = hit.t_exit + EXIT_BIAS;
+        // Else an object was in front of it, the chunk hit is still the next one
+        if (chunk_hit.t != MISS && chunk_hit.t < t_min) {
+            chunk_hit = next_chunk_hit(ray, &march, t_min);
+        }
+        hit = closest_object_hit(ray, t_min, chunk_hit);
+    }
+
+    color = fog(color + transmittance * hit.color.rgb, medium, medium_distance);
+    return RayHit(vec4<f32>(color, 1.0), hit.t, hit.t_exit);
+}
+
+// The alpha of the medium is the opacity of 1 block, so that's the same falloff as the blending
+fn fog(color: vec3<f32>, medium: vec4<f32>, distance: f32) -> vec3<f32> {
+    let density = -log(1.0 - medium.a);
+    return mix(medium.rgb, color, exp(-density * distance));
+}
+
+// The closest of `chunk_hit` & the voxel object hits past t_min
+fn closest_object_hit(ray: Ray, t_min: f32, chunk_hit: RayHit) -> RayHit {
+    var hit = chunk_hit;
+    var offset_ray = ray;
+    offset_ray.origin = ray.origin + ray.dir * t_min;
+
+    for (var i = 0u; i < world_params.object_count; i++) {
+        let object = objects[i];
+
+        let range = intersect_bounds(offset_ray, object.aabb_min, object.aabb_max);
+        if (range.x > range.y || range.y < 0.0 || (hit.t != MISS && t_min + range.x > hit.t)) {
+            continue;
+        }
+
+        // The direction isn't normalized, so t is the same in both spaces
+        var local_ray: Ray;
+        local_ray.origin = (object.inv_model * vec4<f32>(offset_ray.origin, 1.0)).xyz;
+        local_ray.dir = (object.inv_model * vec4<f32>(offset_ray.dir, 0.0)).xyz;
+
+        var object_box: BoundingBox;
+        object_box.min = vec3<f32>(0.0);
+        object_box.size = object.size;
+
+        let object_hit = traverse(local_ray, object_box, OBJECT_POOL, object.root);
+        if (object_hit.t != MISS && (hit.t == MISS || t_min + object_hit.t < hit.t)) {
+            hit = RayHit(object_hit.color, t_min + object_hit.t, t_min + object_hit.t_exit);
+        }
+    }
+
+    return hit;
+}
+
+fn chunk_march_start(ray: Ray) -> ChunkMarch {
+    let grid_size = i32(world_params.grid_size);
+    let chunk_size = f32(CHUNK_SIZE);
+
+    var march: ChunkMarch;
+    march.grid_min = vec3<f32>(world_params.grid_origin) * chunk_size;
+
+    let range = intersect_bounds(ray, march.grid_min, march.grid_min + f32(grid_size) * chunk_size);
+    march.done = range.x > range.y || range.y < 0.0;
+    march.dda = dda_start(ray, march.grid_min, chunk_size, grid_size, max(range.x, 0.0));
+    return march;
+}
+
+// First chunk leaf past t_min, from the current cell of the march. Each non empty chunk is
+// traversed in its own structure
+fn next_chunk_hit(ray: Ray, march: ptr<function, ChunkMarch>, t_min: f32) -> RayHit {
+    let grid_size = i32(world_params.grid_size);
+    let chunk_size = f32(CHUNK_SIZE);
+    var offset_ray = ray;
+    offset_ray.origin = ray.origin + ray.dir * t_min;
+
+    while (!(*march).done) {
+        let cell = (*march).dda.cell;
+        let root = chunk_root(cell);
+        if (root != EMPTY_CHUNK) {
+            var chunk_box: BoundingBox;
+            chunk_box.min = (*march).grid_min + vec3<f32>(cell) * chunk_size;
+            chunk_box.size = chunk_size;
+
+            var hit: RayHit;
+            if (world_params.brickmap != 0u) {
+                hit = traverse_brickmap(offset_ray, chunk_box, root);
+            } else {
+                hit = traverse(offset_ray, chunk_box, WORLD_POOL, root);
+            }
+            if (hit.t != MISS) {
+                return RayHit(hit.color, t_min + hit.t, t_min + hit.t_exit);
+            }
+        }
+
+        (*march).done = !dda_step(&(*march).dda, grid_size);
+    }
+
+    return RayHit(vec4<f32>(get_env_color(ray), 1.0), MISS, MISS);
+}
+
+// First node of the structure of a grid cell, EMPTY_CHUNK if nothing is there
+fn chunk_root(cell: vec3<i32>) -> u32 {
+    let grid_size = i32(world_params.grid_size);
+    return chunk_grid[u32(cell.x + (cell.y + cell.z * grid_size) * grid_size)];
+}
+
+// Word of a brickmap, from the first word of its chunk
+fn fetch_word(root: u32, word: u32) -> u32 {
+    let node = fetch_node(root + word / 2u);
+    return select(node.children_idx, node.color, (word & 1u) == 1u);
+}
+
+// DDA through the 2x2x2 bricks of the chunk, then through the voxels of the non empty ones
+fn traverse_brickmap(ray: Ray, chunk_box: BoundingBox, root: u32) -> RayHit {
+    let miss = RayHit(vec4<f32>(get_env_color(ray), 1.0), MISS, MISS);
+    let range = intersect_aabb(ray, chunk_box);
+    if (range.x > range.y || range.y < 0.0) {
+        return miss;
+    }
+
+    var iteration = 0u;
+    var bricks = dda_start(ray, chunk_box.min, f32(BRICK_SIZE), 2, max(range.x, 0.0));
+    loop {
+        iteration++;
+        let b = bricks.cell;
+        let brick = fetch_word(root, u32(b.x | (b.y << 1u) | (b.z << 2u)));
+
+        if (brick != 0u) {
+            let brick_min = chunk_box.min + vec3<f32>(b * BRICK_SIZE);
+            var voxels = dda_start(ray, brick_min, 1.0, BRICK_SIZE, bricks.t);
+            loop {
+                iteration++;
+                let v = voxels.cell;
+                let index = u32(v.x + (v.y + v.z * BRICK_SIZE) * BRICK_SIZE);
+                let word = fetch_word(root, brick + index / 32u);
+                let bit = 1u << (index % 32u);
+
+                if ((word & bit) != 0u) {
+                    // The colors follow the occupancy, one per set bit
+                    var rank = countOneBits(word & (bit - 1u));
+                    for (var w = 0u; w < index / 32u; w++) {
+                        rank += countOneBits(fetch_word(root, brick + w));
+                    }
+                    let voxel_min = brick_min + vec3<f32>(v);
+                    let t_exit = intersect_bounds(ray, voxel_min, voxel_min + 1.0).y;
+                    let color = fetch_word(root, brick + OCCUPANCY_WORDS + rank);
+                    return leaf_hit(color, voxels.t, t_exit, iteration);
+                }
+
+                if (!dda_step(&voxels, BRICK_SIZE)) {
+                    break;
+                }
+            }
+        }
+
+        if (!dda_step(&bricks, 2)) {
+            break;
+        }
+    }
+
+    return miss;
+}
+
+fn traverse(ray: Ray, root_box: BoundingBox, pool: u32, root: u32) -> RayHit {
+    // Check if intersect the octree
+    let hit = intersect_aabb(ray, root_box);
+    if (hit.x > hit.y || hit.y < 0.0) { // No intersection
+        return RayHit(vec4<f32>(get_env_color(ray), 1.0), MISS, MISS);
+    }
+    // ray.origin = ray.origin + ray.dir * (hit.x + EPSILON);
+
+    var stack: array<StackNode, 16>; // Enough for the chunk & voxel object depths
+    var stack_ptr = 0u;
+
+    // Push root box into the stack
+    stack[stack_ptr].node_idx = root;
+    stack[stack_ptr].box = root_box;
+    stack_ptr++;
+
+    var iteration = 0u;
+
+    /***************** Octree navigation *****************/
+    while (stack_ptr > 0u && iteration < 256) {
+        iteration++;
+
+        // Pop the parent
+        stack_ptr--;
+        let stack_entry = stack[stack_ptr];
+        let current_node = fetch_pool_node(pool, stack_entry.node_idx);
+
+        if (current_node.children_idx == 0xFFFFFFFFu) { // Leaf
+            let range = intersect_aabb(ray, stack_entry.box);
+            return leaf_hit(current_node.color, max(range.x, 0.0), range.y, iteration);
+        }
+
+        if (current_node.children_idx != 0u) { // Non-empty children
+            // return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+            let children_idx = root + current_node.children_idx; // Relative to the root
+            let sorted_children = sort_children(ray);
+            // Push all intersected children in the stack
+            for (var i = 7; i >= 0; i--) {
+                let ci = sorted_children[u32(i)];
+                let curr_box = child_box(stack_entry.box, ci);
+
+                let child_node = fetch_pool_node(pool, children_idx + ci);
+                if (child_node.children_idx == 0u) { // If child is empty
+                    continue;
+                }
+
+                let hit = intersect_aabb(ray, curr_box);
+                if (hit.x <= hit.y + EPSILON && hit.y > 0.0) {
+                    stack[stack_ptr].node_idx = children_idx + ci;
+                    stack[stack_ptr].box = curr_box;
+                    stack_ptr++;
+                }
+            }
+        }
+
+        if (stack_ptr >= 16) { return RayHit(vec4<f32>(0.0, 1.0, 1.0, 1.0), MISS, MISS); } // Safe limit
+    }
+
+    return RayHit(vec4<f32>(get_env_color(ray), 1.0), MISS, MISS);
+}
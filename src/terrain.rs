@@ -0,0 +1,316 @@
+use noise::{NoiseFn, Perlin};
+
+use crate::{chunk::CHUNK_SIZE, voxel::Voxel};
+
+const CLIMATE_SCALE: f64 = 256.0; // Blocks per temperature / humidity noise period, the biome size
+const BLEND_WIDTH: f64 = 0.08; // Climate distance over which 2 biomes are blended
+const STONE: glam::Vec3 = glam::Vec3::new(0.6, 0.6, 0.6);
+const WATER: glam::Vec3 = glam::Vec3::new(0.15, 0.35, 0.65);
+const WATER_ALPHA: f32 = 0.2; // Opacity of 1 voxel of water, about 20 voxels to see through
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+    Plains,
+    Desert,
+    Snow,
+    Forest,
+}
+
+// What a biome looks like & where it is in the (temperature, humidity) plane
+struct BiomeParams {
+    climate: glam::DVec2, // Center, in noise values (about [-0.7, 0.7])
+    surface: glam::Vec3,
+    subsurface: glam::Vec3,
+    subsurface_depth: u32, // Below the surface, then stone
+    base_height: f64,      // Above the sea level
+    amplitude: f64,        // Height variation around the base height
+    noise_scale: f64,      // Blocks per height noise period
+}
+
+// Generation knobs, the same params always give the same world
+#[derive(Debug, Clone)]
+pub struct TerrainParams {
+    pub seed: u32,
+    pub frequency: f64, // Multiplier of the height noise frequencies of the biomes
+    pub amplitude: f64, // Multiplier of the height variations of the biomes
+    pub octaves: u32,   // Height noise layers, each at twice the frequency & half the amplitude
+    pub sea_level: u32, // Height the biome heights are relative to
+}
+
+// Terrain generation: a height & materials per (x, z) column, from the biomes around it
+pub struct Terrain {
+    params: TerrainParams,
+    height: Perlin,
+    temperature: Perlin,
+    humidity: Perlin,
+}
+
+// Vertical layout of a column, from y = 0 up
+pub struct Column {
+    pub biome: Biome, // The one its materials come from
+    pub height: u32,  // Solid voxels, up to the surface
+    pub surface: Voxel,
+    pub subsurface: Voxel,
+    pub subsurface_depth: u32,
+    pub sea_level: u32, // Water from the surface up to it
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [Biome::Plains, Biome::Desert, Biome::Snow, Biome::Forest];
+
+    fn params(self) -> BiomeParams {
+        match self {
+            Biome::Plains => BiomeParams {
+                climate: glam::dvec2(0.0, -0.1),
+                surface: glam::vec3(0.2, 0.8, 0.3),
+                subsurface: glam::vec3(0.5, 0.4, 0.3),
+                subsurface_depth: 3,
+                base_height: 0.5,
+                amplitude: 2.0,
+                noise_scale: 96.0,
+            },
+            Biome::Desert => BiomeParams {
+                climate: glam::dvec2(0.3, -0.3),
+                surface: glam::vec3(0.9, 0.8, 0.5),
+                subsurface: glam::vec3(0.8, 0.65, 0.4),
+                subsurface_depth: 4,
+                base_height: 2.0,
+                amplitude: 3.0,
+                noise_scale: 24.0, // Dunes
+            },
+            Biome::Snow => BiomeParams {
+                climate: glam::dvec2(-0.3, 0.0),
+                surface: glam::vec3(0.85, 0.88, 0.93),
+                subsurface: STONE,
+                subsurface_depth: 0,
+                base_height: 6.0,
+                amplitude: 5.0,
+                noise_scale: 48.0,
+            },
+            Biome::Forest => BiomeParams {
+                climate: glam::dvec2(0.05, 0.25),
+                surface: glam::vec3(0.1, 0.55, 0.2),
+                subsurface: glam::vec3(0.45, 0.35, 0.25),
+                subsurface_depth: 3,
+                base_height: 3.0,
+                amplitude: 4.0,
+                noise_scale: 64.0,
+            },
+        }
+    }
+}
+
+impl Terrain {
+    pub fn new(params: &TerrainParams) -> Self {
+        Self {
+            params: params.clone(),
+            height: Perlin::new(noise_seed(params.seed, 0)),
+            temperature: Perlin::new(noise_seed(params.seed, 1)),
+            humidity: Perlin::new(noise_seed(params.seed, 2)),
+        }
+    }
+
+    // Weight of each biome of `Biome::ALL`, summing to 1. Only the closest biome away from the borders
+    pub fn biome_weights(&self, x: i32, z: i32) -> [f64; 4] {
+        let point = [x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE];
+        let climate = glam::dvec2(self.temperature.get(point), self.humidity.get(point));
+
+        let distances = Biome::ALL.map(|biome| biome.params().climate.distance(climate));
+        let closest = distances.iter().copied().fold(f64::INFINITY, f64::min);
+        let weights = distances.map(|d| (1.0 - (d - closest) / BLEND_WIDTH).max(0.0));
+        let sum: f64 = weights.iter().sum();
+        weights.map(|w| w / sum)
+    }
+
+    pub fn column(&self, x: i32, z: i32) -> Column {
+        let weights = self.biome_weights(x, z);
+
+        // Heights blend smoothly, the materials are dithered by weight so the chunks keep few colors
+        let mut height = 0.0;
+        for (biome, weight) in Biome::ALL.iter().zip(weights) {
+            if weight > 0.0 {
+                let params = biome.params();
+                let point =
+                    glam::dvec2(x as f64, z as f64) * self.params.frequency / params.noise_scale;
+                let amplitude = params.amplitude * self.params.amplitude;
+                height += weight * (params.base_height + amplitude * self.height_noise(point));
+            }
+        }
+
+        let mut threshold = self.hash(x, z, 0);
+        let i = weights
+            .iter()
+            .position(|&weight| {
+                threshold -= weight;
+                threshold < 0.0
+            })
+            .unwrap_or(weights.len() - 1);
+        let biome = Biome::ALL[i];
+        let params = biome.params();
+
+        Column {
+            biome,
+            height: ((self.params.sea_level as f64 + height).round() as u32).clamp(1, CHUNK_SIZE),
+            surface: Voxel::new(params.surface, true),
+            subsurface: Voxel::new(params.subsurface, true),
+            subsurface_depth: params.subsurface_depth,
+            sea_level: self.params.sea_level,
+        }
+    }
+
+    // In [0, 1), the same for a column every time with the same seed. Different salts give
+    // independent values
+    pub fn hash(&self, x: i32, z: i32, salt: u32) -> f64 {
+        column_hash(x, z, (self.params.seed as u64) << 32 | salt as u64)
+    }
+
+    // Octaves summed & normalized, about [-1, 1]
+    fn height_noise(&self, mut point: glam::DVec2) -> f64 {
+        let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+        for _ in 0..self.params.octaves {
+            sum += amplitude * self.height.get(point.to_array());
+            total += amplitude;
+            amplitude *= 0.5;
+            point *= 2.0;
+        }
+        sum / total
+    }
+}
+
+impl Column {
+    // Top of the solid voxels or of the water
+    pub fn top(&self) -> u32 {
+        self.height.max(self.sea_level)
+    }
+
+    pub fn is_underwater(&self) -> bool {
+        self.height < self.sea_level
+    }
+
+    pub fn voxel(&self, y: u32) -> Voxel {
+        if y >= self.height {
+            if y < self.sea_level {
+                Voxel::transparent(WATER, WATER_ALPHA)
+            } else {
+                Voxel::default()
+            }
+        } else if y + 1 == self.height {
+            self.surface
+        } else if y + 1 + self.subsurface_depth >= self.height {
+            self.subsurface
+        } else {
+            Voxel::new(STONE, true)
+        }
+    }
+}
+
+// Seed of the noise `index` of the world, the next world seed doesn't reuse it for another noise
+fn noise_seed(seed: u32, index: u32) -> u32 {
+    (mix((seed as u64) << 32 | index as u64) >> 32) as u32
+}
+
+fn column_hash(x: i32, z: i32, salt: u64) -> f64 {
+    let h =
+        mix(((x as u32 as u64) << 32 | z as u32 as u64) ^ salt.wrapping_mul(0x9e3779b97f4a7c15));
+    (h >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+// MurmurHash3 finalizer
+fn mix(mut h: u64) -> u64 {
+    h = (h ^ (h >> 33)).wrapping_mul(0xff51afd7ed558ccd);
+    h = (h ^ (h >> 33)).wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^ (h >> 33)
+}
+
+impl Default for TerrainParams {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            frequency: 1.0,
+            amplitude: 1.0,
+            octaves: 1,
+            sea_level: 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn terrain(seed: u32) -> Terrain {
+        Terrain::new(&TerrainParams {
+            seed,
+            ..Default::default()
+        })
+    }
+
+    // Voxels of the decorated chunk & the ones it spills into its neighbours, in world space
+    fn chunk_voxels(terrain: &Terrain, chunk_coord: glam::IVec3) -> Vec<(glam::IVec3, Voxel)> {
+        let mut chunk = Chunk::new();
+        chunk.generate(terrain, chunk_coord);
+        let mut voxels = chunk.decorate(terrain, chunk_coord);
+        voxels.extend(chunk.iter_voxels().map(|(index, voxel)| {
+            let local = Chunk::index_to_local_pos(index);
+            (Chunk::local_to_world_pos(&local, &chunk_coord), voxel)
+        }));
+        voxels
+    }
+
+    #[test]
+    fn same_seed_same_chunks() {
+        let chunk_coords = [
+            glam::ivec3(0, 0, 0),
+            glam::ivec3(-1, 0, 2),
+            glam::ivec3(3, 0, -5),
+        ];
+        let (a, b, other) = (terrain(7), terrain(7), terrain(8));
+        for chunk_coord in chunk_coords {
+            assert_eq!(chunk_voxels(&a, chunk_coord), chunk_voxels(&b, chunk_coord));
+        }
+        assert!(
+            chunk_coords
+                .iter()
+                .any(|&chunk_coord| chunk_voxels(&a, chunk_coord)
+                    != chunk_voxels(&other, chunk_coord))
+        );
+    }
+
+    // Changing them changes every existing world
+    #[test]
+    fn pinned_terrain_of_seed_7() {
+        let terrain = terrain(7);
+        for (x, z, biome, height) in [
+            (0, 0, Biome::Plains, 5),
+            (37, -12, Biome::Desert, 6),
+            (-250, 91, Biome::Forest, 7),
+        ] {
+            let column = terrain.column(x, z);
+            assert_eq!((column.biome, column.height), (biome, height), "({x}, {z})");
+        }
+
+        // Column (37, -39): stone, desert subsurface & sand, then air
+        let mut chunk = Chunk::new();
+        chunk.generate(&terrain, glam::ivec3(2, 0, -3));
+        let colors: Vec<_> = (0..10)
+            .map(|y| chunk.get(glam::uvec3(5, y, 9)).color)
+            .collect();
+        let (stone, subsurface, sand) = (0xff999999, 0xff66a5cc, 0xff7fcce5);
+        assert_eq!(
+            colors,
+            [
+                stone, stone, stone, stone, subsurface, subsurface, subsurface, subsurface, sand, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn noise_seeds_are_not_shared_between_world_seeds() {
+        let seeds = |seed| (0..3).map(move |index| noise_seed(seed, index));
+        for seed in [0, 1, 7, u32::MAX - 1] {
+            let next: Vec<_> = seeds(seed + 1).collect();
+            assert!(seeds(seed).all(|noise| !next.contains(&noise)));
+        }
+    }
+}
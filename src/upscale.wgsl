@@ -0,0 +1,82 @@
+// ===========================
+// Structures
+// ===========================
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+struct TargetUniform {
+    uv_scale: vec2<f32>,
+    uv_max: vec2<f32>,
+    viewport: vec2<u32>,
+    loading: u32, // Draw the loading bar
+    loading_progress: f32,
+};
+
+// Screen space, y down
+const LOADING_BAR_MIN: vec2<f32> = vec2<f32>(0.3, 0.9);
+const LOADING_BAR_MAX: vec2<f32> = vec2<f32>(0.7, 0.92);
+
+// ===========================
+// Bindings
+// ===========================
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: TargetUniform;
+@group(0) @binding(3)
+var source_depth: texture_2d<f32>; // Depth32Float (fragment path) or R32Float (compute path)
+
+// ===========================
+// Vertex shader
+// ===========================
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Render a giant triangle as the canvas
+    let x = f32(i32(vertex_index == 1u)) * 4.0 - 1.0;
+    let y = f32(i32(vertex_index == 2u)) * 4.0 - 1.0;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5); // Texture space, y down
+    return out;
+}
+
+// ===========================
+// Fragment shader
+// ===========================
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // Only the viewport of the source texture holds the current frame
+    let uv = min(in.uv * params.uv_scale, params.uv_max);
+
+    // Depth can't be interpolated, take the nearest raymarched pixel
+    let texel = min(
+        vec2<u32>(uv * vec2<f32>(textureDimensions(source_depth))),
+        params.viewport - 1u,
+    );
+
+    var out: FragmentOutput;
+    out.color = textureSample(source, source_sampler, uv);
+    out.depth = textureLoad(source_depth, texel, 0).r;
+
+    // In front of everything, filled from the left up to the progress
+    if (params.loading != 0u && all(in.uv >= LOADING_BAR_MIN) && all(in.uv <= LOADING_BAR_MAX)) {
+        let x = (in.uv.x - LOADING_BAR_MIN.x) / (LOADING_BAR_MAX.x - LOADING_BAR_MIN.x);
+        out.color = select(vec4<f32>(0.1, 0.1, 0.1, 1.0), vec4<f32>(1.0), x <= params.loading_progress);
+        out.depth = 0.0;
+    }
+    return out;
+}
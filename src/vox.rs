@@ -0,0 +1,293 @@
+use anyhow::Context;
+use std::{collections::HashMap, path::Path};
+
+// MagicaVoxel .vox files: https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
+// Only the models are read, the scene graph (nTRN, nGRP, nSHP) & materials are ignored
+
+const MAX_MODEL_SIZE: u32 = 256;
+const VERSION: u32 = 150;
+
+// One model of a .vox file, converted to Y up
+pub struct VoxModel {
+    pub size: glam::UVec3,
+    pub voxels: Vec<(glam::UVec3, u32)>, // Position, color packed like `Voxel::color`
+}
+
+pub fn load(path: &Path) -> anyhow::Result<Vec<VoxModel>> {
+    let bytes = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    parse(&bytes).with_context(|| format!("Invalid .vox file {}", path.display()))
+}
+
+fn parse(bytes: &[u8]) -> anyhow::Result<Vec<VoxModel>> {
+    let mut reader = Reader { bytes, offset: 0 };
+    anyhow::ensure!(reader.take(4)? == b"VOX ", "Missing VOX header");
+    let _version = reader.u32()?;
+
+    // MAIN contains all the other chunks as children
+    let (id, content, children) = reader.chunk()?;
+    anyhow::ensure!(id == b"MAIN", "Missing MAIN chunk");
+    anyhow::ensure!(content.is_empty(), "Unexpected MAIN content");
+
+    let mut sizes = Vec::new();
+    let mut models = Vec::new(); // Voxels with their palette index, (x, y, z) Z up
+    let mut palette = default_palette();
+
+    let mut reader = Reader {
+        bytes: children,
+        offset: 0,
+    };
+    while !reader.is_empty() {
+        let (id, content, _) = reader.chunk()?;
+        let mut content = Reader {
+            bytes: content,
+            offset: 0,
+        };
+
+        match id {
+            b"SIZE" => sizes.push(glam::uvec3(content.u32()?, content.u32()?, content.u32()?)),
+            b"XYZI" => {
+                let count = content.u32()? as usize;
+                let voxels = content.take(count * 4)?;
+                models.push(
+                    voxels
+                        .chunks_exact(4)
+                        .map(|v| [v[0], v[1], v[2], v[3]])
+                        .collect::<Vec<_>>(),
+                );
+            }
+            b"RGBA" => {
+                // Color i of the chunk is palette index i + 1
+                for i in 0..255 {
+                    palette[i + 1] = content.u32()?;
+                }
+            }
+            _ => (),
+        }
+    }
+    anyhow::ensure!(
+        sizes.len() == models.len(),
+        "{} SIZE chunks for {} XYZI chunks",
+        sizes.len(),
+        models.len()
+    );
+
+    Ok(sizes
+        .into_iter()
+        .zip(models)
+        .map(|(size, voxels)| VoxModel {
+            size: glam::uvec3(size.x, size.z, size.y),
+            voxels: voxels
+                .into_iter()
+                .filter(|&[x, y, z, _]| {
+                    (x as u32) < size.x && (y as u32) < size.y && (z as u32) < size.z
+                })
+                .map(|[x, y, z, index]| {
+                    // Z up -> Y up, the Y axis is flipped to keep the model from being mirrored
+                    let position = glam::uvec3(x as u32, z as u32, size.y - 1 - y as u32);
+                    (position, palette[index as usize])
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+// Single model file, colors beyond the 255 of the palette are replaced by the closest one
+pub fn save(path: &Path, model: &VoxModel) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        model.size.min_element() > 0 && model.size.max_element() <= MAX_MODEL_SIZE,
+        "A .vox model size must be in [1, {MAX_MODEL_SIZE}], not {}",
+        model.size
+    );
+
+    let mut palette = Vec::with_capacity(255);
+    let mut indices = HashMap::new();
+    let mut xyzi = Vec::with_capacity(4 + model.voxels.len() * 4);
+    xyzi.extend((model.voxels.len() as u32).to_le_bytes());
+
+    for (position, color) in &model.voxels {
+        let index = *indices.entry(*color).or_insert_with(|| {
+            if palette.len() < 255 {
+                palette.push(*color);
+                palette.len() as u8
+            } else {
+                closest_color(&palette, *color)
+            }
+        });
+
+        // Y up -> Z up, inverse of `parse`
+        let (x, y, z) = (position.x, model.size.z - 1 - position.z, position.y);
+        xyzi.extend([x as u8, y as u8, z as u8, index]);
+    }
+
+    let size: Vec<u8> = [model.size.x, model.size.z, model.size.y]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let mut rgba = vec![0; 256 * 4];
+    for (i, color) in palette.iter().enumerate() {
+        rgba[i * 4..i * 4 + 4].copy_from_slice(&color.to_le_bytes());
+    }
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &size, &[]);
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    write_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+    let mut bytes = Vec::with_capacity(children.len() + 20);
+    bytes.extend(b"VOX ");
+    bytes.extend(VERSION.to_le_bytes());
+    write_chunk(&mut bytes, b"MAIN", &[], &children);
+
+    std::fs::write(path, bytes).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((content.len() as u32).to_le_bytes());
+    bytes.extend((children.len() as u32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+}
+
+// Palette index (from 1) of the closest color
+fn closest_color(palette: &[u32], color: u32) -> u8 {
+    let distance = |other: u32| {
+        (0..3)
+            .map(|channel| {
+                let a = (color >> (channel * 8)) & 0xFF;
+                let b = (other >> (channel * 8)) & 0xFF;
+                a.abs_diff(b).pow(2)
+            })
+            .sum::<u32>()
+    };
+
+    let (i, _) = palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, other)| distance(**other))
+        .unwrap();
+    i as u8 + 1
+}
+
+// Palette used when the file has no RGBA chunk: a 6x6x6 color cube without black, then 4 ramps
+// of 10 shades for red, green, blue and gray (same as MagicaVoxel)
+fn default_palette() -> [u32; 256] {
+    const CUBE: [u32; 6] = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u32; 10] = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let rgba = |r: u32, g: u32, b: u32| (0xFF << 24) | (b << 16) | (g << 8) | r;
+
+    let mut palette = [0; 256];
+    let mut colors = palette.iter_mut().skip(1);
+    for r in CUBE {
+        for g in CUBE {
+            for b in CUBE {
+                if r != 0 || g != 0 || b != 0 {
+                    *colors.next().unwrap() = rgba(r, g, b);
+                }
+            }
+        }
+    }
+    for (r, g, b) in [(1, 0, 0), (0, 1, 0), (0, 0, 1), (1, 1, 1)] {
+        for v in RAMP {
+            *colors.next().unwrap() = rgba(v * r, v * g, v * b);
+        }
+    }
+
+    palette
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .context("Unexpected end of file")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // (id, content, children)
+    fn chunk(&mut self) -> anyhow::Result<(&'a [u8], &'a [u8], &'a [u8])> {
+        let id = self.take(4)?;
+        let content_size = self.u32()? as usize;
+        let children_size = self.u32()? as usize;
+        Ok((id, self.take(content_size)?, self.take(children_size)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: u32 = 0xFF0000FF;
+    const GREEN: u32 = 0xFF00FF00;
+
+    fn save_and_read(name: &str, model: &VoxModel) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("{}_{name}.vox", std::process::id()));
+        save(&path, model).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn save_then_load_gives_the_same_model() {
+        let model = VoxModel {
+            size: glam::uvec3(2, 3, 4),
+            voxels: vec![
+                (glam::uvec3(1, 2, 3), RED),
+                (glam::uvec3(0, 0, 0), GREEN),
+                (glam::uvec3(1, 0, 2), RED),
+            ],
+        };
+        let bytes = save_and_read("roundtrip", &model);
+
+        // The file is Z up: SIZE is (x, z, y) & the Y axis is flipped
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(&bytes[20..24], b"SIZE");
+        assert_eq!([u32_at(32), u32_at(36), u32_at(40)], [2, 4, 3]);
+        assert_eq!(&bytes[44..48], b"XYZI");
+        assert_eq!(u32_at(56), 3);
+        // One palette entry per color, from 1
+        assert_eq!(bytes[60..72], [1, 0, 2, 1, 0, 3, 0, 2, 1, 1, 0, 1]);
+
+        let loaded = parse(&bytes).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].size, model.size);
+        assert_eq!(loaded[0].voxels, model.voxels);
+    }
+
+    #[test]
+    fn colors_past_the_palette_take_the_closest_one() {
+        let voxels: Vec<_> = (0..256)
+            .map(|i| (glam::uvec3(i % 16, i / 16, 0), 0xFF000000 | i))
+            .collect();
+        let model = VoxModel {
+            size: glam::uvec3(16, 16, 1),
+            voxels,
+        };
+
+        let colors = |model: &VoxModel| -> Vec<u32> {
+            model.voxels.iter().map(|(_, color)| *color).collect()
+        };
+        let loaded = colors(&parse(&save_and_read("palette", &model)).unwrap()[0]);
+        let saved = colors(&model);
+        assert_eq!(loaded[..255], saved[..255]);
+        assert_eq!(loaded[255], saved[254]);
+    }
+}
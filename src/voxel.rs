@@ -1,5 +1,5 @@
 #[repr(C)]
-#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Voxel {
     pub color: u32,
 }
@@ -18,4 +18,22 @@ impl Voxel {
         let color = (a << 24) | (b << 16) | (g << 8) | r;
         Self { color }
     }
+
+    // Water, glass... `alpha` is the opacity of 1 voxel of thickness, the renderer blends the
+    // voxels behind it
+    pub fn transparent(color: glam::Vec3, alpha: f32) -> Self {
+        let a = (alpha.clamp(0.0, 1.0) * 254.0).round().max(1.0) as u32; // Neither air nor opaque
+        Self {
+            color: Self::new(color, true).color & 0x00FFFFFF | a << 24,
+        }
+    }
+
+    // Alpha 0
+    pub fn is_air(&self) -> bool {
+        self.color >> 24 == 0
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.color >> 24 == 255
+    }
 }
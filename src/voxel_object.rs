@@ -0,0 +1,196 @@
+use std::path::Path;
+
+use crate::{
+    svo::{Svo, SvoNode},
+    vox,
+    world_position::WorldPosition,
+};
+
+pub type VoxelModelId = usize;
+
+const NODE_POOL_SIZE: u64 = 4_000_000; // Shared by all the models, in bytes
+const MAX_VOXEL_OBJECTS: usize = 256;
+
+// Voxel model with its own octree, in model space [0, size)
+pub struct VoxelModel {
+    svo: Svo,
+    depth: u32,
+    size: glam::UVec3,
+}
+
+// Instance of a voxel model, moved without rebuilding the world octree
+pub struct VoxelObject {
+    pub model: VoxelModelId,
+    pub position: WorldPosition, // Center of the model
+    pub rotation: glam::Quat,
+    pub scale: f32, // World size of a voxel
+}
+
+// Octrees of all the models & the instance list, bound with the world
+pub struct VoxelObjectPool {
+    pub node_buffer: wgpu::Buffer,
+    pub instance_buffer: wgpu::Buffer,
+    models: Vec<PooledModel>,
+    node_count: u32,
+}
+
+struct PooledModel {
+    root: u32,
+    depth: u32,
+    size: glam::UVec3,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VoxelObjectInstance {
+    inv_model: [[f32; 4]; 4], // Render space -> model space
+    aabb_min: [f32; 3],       // Render space bounds
+    root: u32,
+    aabb_max: [f32; 3],
+    size: f32, // Root box size in model space
+}
+
+impl VoxelModel {
+    // All the models of a .vox file
+    pub fn load(path: &Path) -> anyhow::Result<Vec<Self>> {
+        let models = vox::load(path)?;
+        anyhow::ensure!(!models.is_empty(), "{} has no models", path.display());
+
+        models.iter().map(Self::from_vox).collect()
+    }
+
+    pub fn from_vox(model: &vox::VoxModel) -> anyhow::Result<Self> {
+        let depth = model.size.max_element().max(2).next_power_of_two().ilog2();
+
+        let mut svo = Svo::new();
+        for (position, color) in &model.voxels {
+            svo.insert(position.as_ivec3(), *color, depth)?;
+        }
+
+        Ok(Self {
+            svo,
+            depth,
+            size: model.size,
+        })
+    }
+}
+
+impl VoxelObject {
+    pub fn new(model: VoxelModelId, position: WorldPosition) -> Self {
+        Self {
+            model,
+            position,
+            rotation: glam::Quat::IDENTITY,
+            scale: 1.0,
+        }
+    }
+}
+
+impl VoxelObjectPool {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let node_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Voxel Object Node Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            size: NODE_POOL_SIZE,
+            mapped_at_creation: false,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Voxel Object Instance Buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            size: (MAX_VOXEL_OBJECTS * size_of::<VoxelObjectInstance>()) as u64,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            node_buffer,
+            instance_buffer,
+            models: Vec::new(),
+            node_count: 0,
+        }
+    }
+
+    pub fn add_model(
+        &mut self,
+        queue: &wgpu::Queue,
+        model: &VoxelModel,
+    ) -> anyhow::Result<VoxelModelId> {
+        // Child indices are relative to the root, the nodes are copied as is
+        let nodes = &model.svo.nodes;
+        let offset = self.node_count as u64 * size_of::<SvoNode>() as u64;
+        let size = model.svo.size() as u64;
+        anyhow::ensure!(
+            offset + size <= NODE_POOL_SIZE,
+            "Voxel object pool is full ({} kB)",
+            NODE_POOL_SIZE / 1024
+        );
+
+        queue.write_buffer(&self.node_buffer, offset, bytemuck::cast_slice(nodes));
+        self.models.push(PooledModel {
+            root: self.node_count,
+            depth: model.depth,
+            size: model.size,
+        });
+        self.node_count += nodes.len() as u32;
+
+        Ok(self.models.len() - 1)
+    }
+
+    // Return the number of instances written, the objects over the limit are skipped
+    pub fn upload(
+        &self,
+        queue: &wgpu::Queue,
+        objects: &[VoxelObject],
+        render_origin: glam::IVec3,
+    ) -> u32 {
+        let instances: Vec<_> = objects
+            .iter()
+            .filter_map(|object| {
+                Some(
+                    self.models
+                        .get(object.model)?
+                        .instance(object, render_origin),
+                )
+            })
+            .take(MAX_VOXEL_OBJECTS)
+            .collect();
+
+        if !instances.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        }
+        instances.len() as u32
+    }
+}
+
+impl PooledModel {
+    // In the render space of `render_origin`
+    fn instance(&self, object: &VoxelObject, render_origin: glam::IVec3) -> VoxelObjectInstance {
+        let size = self.size.as_vec3();
+        let model = glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::splat(object.scale),
+            object.rotation,
+            object.position.relative_to(render_origin),
+        ) * glam::Mat4::from_translation(-size / 2.0);
+
+        // Bounds of the model box
+        let mut aabb_min = glam::Vec3::INFINITY;
+        let mut aabb_max = glam::Vec3::NEG_INFINITY;
+        for i in 0..8 {
+            let corner = glam::Vec3::select(
+                glam::BVec3::new(i & 4 != 0, i & 2 != 0, i & 1 != 0),
+                size,
+                glam::Vec3::ZERO,
+            );
+            let corner = model.transform_point3(corner);
+            aabb_min = aabb_min.min(corner);
+            aabb_max = aabb_max.max(corner);
+        }
+
+        VoxelObjectInstance {
+            inv_model: model.inverse().to_cols_array_2d(),
+            aabb_min: aabb_min.to_array(),
+            root: self.root,
+            aabb_max: aabb_max.to_array(),
+            size: 2_u32.pow(self.depth) as f32,
+        }
+    }
+}
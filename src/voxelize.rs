@@ -0,0 +1,203 @@
+use anyhow::Context;
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use crate::{obj::Obj, vox::VoxModel};
+
+const SAMPLE_SPACING: f32 = 0.5; // In voxels, to hit every voxel crossed by a triangle
+
+struct Triangle {
+    positions: [glam::Vec3; 3],
+    colors: [glam::Vec3; 3],
+    uvs: Option<[glam::Vec2; 3]>,
+    texture: Option<usize>,
+}
+
+// RGBA8 image sampled with wrapping, for the diffuse textures
+struct Texture {
+    size: glam::UVec2,
+    pixels: Vec<u8>,
+}
+
+// Voxelize the surface of an OBJ, `resolution` voxels along its longest side
+pub fn voxelize_obj(path: &Path, resolution: u32) -> anyhow::Result<VoxModel> {
+    let (triangles, textures) = load_triangles(path)?;
+    anyhow::ensure!(!triangles.is_empty(), "{} has no triangles", path.display());
+
+    // Fit the mesh bounds in [0, resolution)
+    let mut min = glam::Vec3::INFINITY;
+    let mut max = glam::Vec3::NEG_INFINITY;
+    for position in triangles.iter().flat_map(|t| t.positions) {
+        min = min.min(position);
+        max = max.max(position);
+    }
+    let scale = resolution as f32 / (max - min).max_element().max(f32::EPSILON);
+    let size = ((max - min) * scale)
+        .ceil()
+        .as_uvec3()
+        .clamp(glam::UVec3::ONE, glam::UVec3::splat(resolution));
+
+    let mut voxels = HashMap::new();
+    for triangle in &triangles {
+        let [a, b, c] = triangle.positions.map(|p| (p - min) * scale);
+        let longest_edge = (b - a).length().max((c - b).length()).max((a - c).length());
+        let steps = (longest_edge / SAMPLE_SPACING).ceil().max(1.0) as u32;
+
+        // Barycentric grid over the triangle
+        for i in 0..=steps {
+            for j in 0..=steps - i {
+                let (u, v) = (i as f32 / steps as f32, j as f32 / steps as f32);
+                let weights = glam::vec3(1.0 - u - v, u, v);
+
+                let position = a * weights.x + b * weights.y + c * weights.z;
+                let voxel = position.floor().as_uvec3().min(size - 1);
+                voxels
+                    .entry(voxel)
+                    .or_insert_with(|| triangle.sample(weights, &textures));
+            }
+        }
+    }
+
+    Ok(VoxModel {
+        size,
+        voxels: voxels.into_iter().collect(),
+    })
+}
+
+// Triangles of all the models with their colors, and the diffuse textures they use
+fn load_triangles(path: &Path) -> anyhow::Result<(Vec<Triangle>, Vec<Texture>)> {
+    let obj = Obj::load(path)?;
+
+    // Texture paths are relative to the OBJ
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut textures = Vec::new();
+    let mut material_textures = Vec::with_capacity(obj.materials.len());
+    for material in &obj.materials {
+        let texture = match &material.diffuse_texture {
+            Some(name) => match Texture::load(&directory.join(name)) {
+                Ok(texture) => {
+                    textures.push(texture);
+                    Some(textures.len() - 1)
+                }
+                Err(e) => {
+                    log::warn!("{e:#}, using the diffuse color");
+                    None
+                }
+            },
+            None => None,
+        };
+        material_textures.push(texture);
+    }
+
+    let mut triangles = Vec::new();
+    for model in &obj.models {
+        let mesh = &model.mesh;
+        let diffuse = obj.diffuse(mesh);
+        let texture = mesh
+            .material_id
+            .filter(|&id| id < obj.materials.len())
+            .and_then(|id| material_textures[id]);
+
+        let vec3 =
+            |values: &[f32], i: usize| values.get(i * 3..i * 3 + 3).map(glam::Vec3::from_slice);
+        let vec2 =
+            |values: &[f32], i: usize| values.get(i * 2..i * 2 + 2).map(glam::Vec2::from_slice);
+
+        for indices in mesh.indices.chunks_exact(3) {
+            let indices = [0, 1, 2].map(|i| indices[i] as usize);
+            let uvs = indices.map(|i| vec2(&mesh.texcoords, i));
+
+            triangles.push(Triangle {
+                positions: indices.map(|i| vec3(&mesh.positions, i).unwrap_or_default()),
+                // The texture is tinted by the vertex colors, if any
+                colors: indices.map(|i| {
+                    vec3(&mesh.vertex_color, i).unwrap_or(if texture.is_some() {
+                        glam::Vec3::ONE
+                    } else {
+                        diffuse
+                    })
+                }),
+                uvs: match uvs {
+                    [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                    _ => None,
+                },
+                texture,
+            });
+        }
+    }
+
+    Ok((triangles, textures))
+}
+
+impl Triangle {
+    // Color packed like `Voxel::color`, at the barycentric `weights`
+    fn sample(&self, weights: glam::Vec3, textures: &[Texture]) -> u32 {
+        let mut color =
+            self.colors[0] * weights.x + self.colors[1] * weights.y + self.colors[2] * weights.z;
+
+        if let (Some(texture), Some([a, b, c])) = (self.texture, self.uvs) {
+            let uv = a * weights.x + b * weights.y + c * weights.z;
+            color *= textures[texture].sample(uv);
+        }
+
+        let [r, g, b] = (color.clamp(glam::Vec3::ZERO, glam::Vec3::ONE) * 255.0)
+            .round()
+            .as_uvec3()
+            .to_array();
+        (0xFF << 24) | (b << 16) | (g << 8) | r
+    }
+}
+
+impl Texture {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .with_context(|| format!("Invalid PNG {}", path.display()))?;
+        let mut buffer = vec![0; reader.output_buffer_size().context("PNG too large")?];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        // Everything to RGBA
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+            png::ColorType::Indexed => anyhow::bail!("Unexpected indexed PNG {}", path.display()),
+        };
+
+        Ok(Self {
+            size: glam::uvec2(info.width, info.height),
+            pixels,
+        })
+    }
+
+    // Nearest texel, OBJ texture coordinates have v up
+    fn sample(&self, uv: glam::Vec2) -> glam::Vec3 {
+        // Repeat outside of [0, 1], the edges stay on their own side
+        let uv = if uv.cmpge(glam::Vec2::ZERO).all() && uv.cmple(glam::Vec2::ONE).all() {
+            uv
+        } else {
+            uv.rem_euclid(glam::Vec2::ONE)
+        };
+        let texel = (glam::vec2(uv.x, 1.0 - uv.y) * self.size.as_vec2())
+            .as_uvec2()
+            .min(self.size - 1);
+
+        let i = ((texel.y * self.size.x + texel.x) * 4) as usize;
+        glam::Vec3::new(
+            self.pixels[i] as f32,
+            self.pixels[i + 1] as f32,
+            self.pixels[i + 2] as f32,
+        ) / 255.0
+    }
+}
@@ -1,87 +1,615 @@
-use crate::{chunk::*, svo::SVO};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+    time::Instant,
+};
+
+use crate::{
+    chunk::*,
+    chunk_generator::{ChunkGenerator, GeneratedChunk},
+    chunk_pool::ChunkPool,
+    config::Config,
+    svo::NodeLayout,
+    terrain::{Terrain, TerrainParams},
+    vox::{self, VoxModel},
+    voxel::Voxel,
+    voxel_object::{VoxelModel, VoxelModelId, VoxelObject, VoxelObjectPool},
+    voxelize,
+    world_position::WorldPosition,
+};
 use dashmap::DashMap;
-use noise::Perlin;
+use rayon::prelude::*;
 use wgpu::util::DeviceExt;
 
 pub struct World {
     chunks: DashMap<glam::IVec3, Chunk>,
-    octree: SVO,
     pub params: WorldParams,
+    dirty_chunks: HashSet<glam::IVec3>, // Edited since their structures were built
+    background: Option<BackgroundGeneration>,
+    // Of the terrain chunks generated in the background, applied again each time they are generated
+    // (they are unloaded out of range). Never dropped
+    terrain_edits: HashMap<glam::IVec3, HashMap<glam::UVec3, Voxel>>,
+    spilled_features: HashMap<glam::IVec3, HashMap<glam::UVec3, Voxel>>, // Same, only placed into air
+}
+
+// Terrain chunks generated around the player while the game runs
+struct BackgroundGeneration {
+    generator: ChunkGenerator,
+    requested: HashSet<glam::IVec3>, // Queued or running, until integrated or cancelled
+    focus: Option<glam::IVec3>,
+    start: Instant,    // Of the current batch of requests
+    batch_size: usize, // Chunks requested since the queue was last empty, minus the cancelled ones
 }
 
 pub struct WorldResource {
     pub layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
-    svo_buffer: wgpu::Buffer,
+    chunk_pool: ChunkPool,
+    uniform: WorldUniform,
     uniform_buffer: wgpu::Buffer,
+    voxel_objects: VoxelObjectPool,
+    grid_origin: glam::IVec3, // In world space, the uniform is relative to the render origin
+    render_origin: glam::IVec3,
 }
 
+#[derive(Debug, Clone)]
 pub struct WorldParams {
     pub view_distance: u32,
+    pub origin: glam::IVec3, // Chunk at the min corner of the view_distance³ chunk grid
+    pub chunk_structure: ChunkStructure,
+    pub svo_layout: SvoLayout, // Of the chunk octrees
+    pub terrain: TerrainParams,
+}
+
+// Acceleration structure of the chunks, traced on the GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStructure {
+    Octree,   // Down to the single voxels
+    Brickmap, // Dense 8³ bit-packed bricks
+}
+
+// How the chunk octrees are built, which decides the order of their nodes in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvoLayout {
+    Insertion,          // Top-down `Svo::insert`, nodes in insertion order
+    Morton(NodeLayout), // Bottom-up from the Morton sorted voxels
+}
+
+impl SvoLayout {
+    pub const ALL: [SvoLayout; 3] = [
+        SvoLayout::Insertion,
+        SvoLayout::Morton(NodeLayout::BreadthFirst),
+        SvoLayout::Morton(NodeLayout::DepthFirst),
+    ];
 }
 
 #[repr(C, align(16))]
 #[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct WorldUniform {
     pub view_distance: u32,
-    _padding0: [u32; 3],
+    pub object_count: u32,
+    pub heatmap: u32,  // Debug view, iteration count instead of the voxel colors
+    pub brickmap: u32, // Chunk structure, brickmaps instead of octrees
+    pub grid_origin: [i32; 3], // Relative to the render origin
+    _padding: u32,
+}
+
+// Voxel storage of the chunks, without their acceleration structures
+pub struct MemoryStats {
+    pub chunks: usize,
+    pub uniform_chunks: usize, // Stored as a single voxel
+    pub bytes: usize,
 }
 
 impl World {
-    pub fn new() -> Self {
-        let params = WorldParams::default();
+    pub fn new(params: WorldParams) -> Self {
+        let chunks = Self::generate_chunks(&params);
+        Self::build_structures(&chunks, &params);
+        log::info!("{}", MemoryStats::new(&chunks));
+
+        Self {
+            chunks,
+            params,
+            dirty_chunks: HashSet::new(),
+            background: None,
+            terrain_edits: HashMap::new(),
+            spilled_features: HashMap::new(),
+        }
+    }
+
+    // Empty world, the terrain chunks are generated in the background by `update_generation`
+    pub fn new_in_background(params: WorldParams) -> Self {
+        Self {
+            chunks: DashMap::new(),
+            background: Some(BackgroundGeneration {
+                generator: ChunkGenerator::new(params.clone()),
+                requested: HashSet::new(),
+                focus: None,
+                start: Instant::now(),
+                batch_size: 0,
+            }),
+            params,
+            dirty_chunks: HashSet::new(),
+            terrain_edits: HashMap::new(),
+            spilled_features: HashMap::new(),
+        }
+    }
+
+    pub fn generate_chunks(params: &WorldParams) -> DashMap<glam::IVec3, Chunk> {
         let chunks = DashMap::new();
-        let perlin = Perlin::new(1);
-
-        for x in 0..params.view_distance {
-            for z in 0..params.view_distance {
-                for y in 0..1 {
-                    let chunk_coord = glam::IVec3::new(x as i32, y as i32, z as i32);
-                    let mut chunk = Chunk::new();
-                    chunk.generate(&perlin, chunk_coord);
-                    chunks.insert(chunk_coord, chunk);
+        let terrain = Terrain::new(&params.terrain);
+
+        let chunk_coords: Vec<_> = params.terrain_chunks().collect();
+        let spilled: Vec<_> = chunk_coords
+            .par_iter()
+            .map(|&chunk_coord| {
+                let mut chunk = Chunk::new();
+                chunk.generate(&terrain, chunk_coord);
+                let spilled = chunk.decorate(&terrain, chunk_coord);
+                chunks.insert(chunk_coord, chunk);
+                spilled
+            })
+            .collect();
+
+        // All the terrain chunks exist by now, nothing to defer
+        for (position, voxel) in spilled.into_iter().flatten() {
+            let (chunk_coord, local) = Self::split_position(position);
+            if params.contains_chunk(chunk_coord) {
+                place_into_air(&chunks, chunk_coord, local, voxel);
+            }
+        }
+
+        chunks
+    }
+
+    // Center the chunk grid horizontally on `focus`, queue its terrain chunks that are missing, cancel
+    // & unload the ones out of it. Only does something when the focus moves to another chunk. Return
+    // the chunks to update on the GPU: the unloaded ones & the ones the grid reached. The chunks above
+    // the terrain only hold edits & spilled features, they stay loaded outside of the grid
+    pub fn update_generation(&mut self, focus: glam::IVec3) -> Vec<glam::IVec3> {
+        let Some(background) = &mut self.background else {
+            return Vec::new();
+        };
+        if background.focus == Some(focus) {
+            return Vec::new();
+        }
+        background.focus = Some(focus);
+
+        let half = self.params.view_distance as i32 / 2;
+        let previous = self.params.clone();
+        self.params.origin = glam::ivec3(focus.x - half, self.params.origin.y, focus.z - half);
+        let params = &self.params;
+        let in_range = |chunk_coord: glam::IVec3| params.contains_chunk(chunk_coord);
+
+        let mut changed = Vec::new();
+        let mut unloaded = Vec::new();
+        for entry in self.chunks.iter() {
+            let chunk_coord = *entry.key();
+            if params.is_terrain_chunk(chunk_coord) && !in_range(chunk_coord) {
+                unloaded.push(chunk_coord);
+            } else if in_range(chunk_coord) && !previous.contains_chunk(chunk_coord) {
+                changed.push(chunk_coord);
+            }
+        }
+        for chunk_coord in &unloaded {
+            self.chunks.remove(chunk_coord);
+            self.dirty_chunks.remove(chunk_coord);
+        }
+        changed.extend(unloaded);
+        background.generator.retain(focus, in_range);
+        let requested = background.requested.len();
+        background
+            .requested
+            .retain(|chunk_coord| in_range(*chunk_coord));
+        background.batch_size -= requested - background.requested.len();
+
+        let missing: Vec<_> = params
+            .terrain_chunks()
+            .filter(|chunk_coord| {
+                !self.chunks.contains_key(chunk_coord)
+                    && !background.requested.contains(chunk_coord)
+            })
+            .collect();
+        if missing.is_empty() {
+            return changed;
+        }
+        if background.requested.is_empty() {
+            background.start = Instant::now();
+            background.batch_size = 0;
+        }
+        background.batch_size += missing.len();
+        background.requested.extend(missing.iter().copied());
+        background.generator.request(missing);
+        changed
+    }
+
+    // Move the next generated chunk into the world, with its edits & the features its neighbours
+    // spilled into it. None if no chunk is ready
+    pub fn integrate_generated_chunk(&mut self) -> Option<glam::IVec3> {
+        let background = self.background.as_mut()?;
+        let GeneratedChunk {
+            chunk_coord,
+            mut chunk,
+            spilled,
+        } = loop {
+            let generated = background.generator.try_recv()?;
+            // Else cancelled after it was sent
+            if background.requested.remove(&generated.chunk_coord) {
+                break generated;
+            }
+        };
+        let batch_done = background.requested.is_empty();
+
+        let features = self.spilled_features.get(&chunk_coord);
+        let edits = self.terrain_edits.get(&chunk_coord);
+        for (local, voxel) in features.into_iter().flatten() {
+            if chunk.get(*local).is_air() {
+                chunk.set(*local, *voxel);
+            }
+        }
+        for (local, voxel) in edits.into_iter().flatten() {
+            chunk.set(*local, *voxel);
+        }
+        if features.is_some() || edits.is_some() {
+            chunk.rebuild_structure(&self.params);
+        }
+        self.chunks.insert(chunk_coord, chunk);
+
+        // The neighbours are rebuilt with the dirty chunks
+        for (position, voxel) in spilled {
+            self.place_spilled_voxel(position, voxel);
+        }
+
+        if let Some(background) = &self.background
+            && batch_done
+        {
+            log::info!(
+                "Terrain generated in {:.2?}: {}",
+                background.start.elapsed(),
+                MemoryStats::new(&self.chunks)
+            );
+        }
+        Some(chunk_coord)
+    }
+
+    // (generated, requested) chunks of the current batch, None once they are all in the world
+    pub fn generation_progress(&self) -> Option<(usize, usize)> {
+        let background = self.background.as_ref()?;
+        if background.requested.is_empty() {
+            return None;
+        }
+        let remaining = background.requested.len();
+        Some((background.batch_size - remaining, background.batch_size))
+    }
+
+    // Generated world with the .vox & mesh imports of the config. In the background the imports are
+    // applied as their chunks get generated
+    pub fn from_config(config: &Config, background: bool) -> anyhow::Result<Self> {
+        let mut world = match background {
+            true => Self::new_in_background(config.world.clone()),
+            false => Self::new(config.world.clone()),
+        };
+        if let Some(path) = &config.import_vox {
+            world.import_vox(path, config.import_origin)?;
+        }
+        if let Some(path) = &config.voxelize {
+            world.import_mesh(path, config.voxelize_resolution, config.import_origin)?;
+        }
+        world.rebuild_dirty_chunks();
+        Ok(world)
+    }
+
+    pub fn import_vox(&mut self, path: &Path, origin: glam::IVec3) -> anyhow::Result<()> {
+        let models = vox::load(path)?;
+        self.place_models(&models, origin, path);
+        Ok(())
+    }
+
+    // Voxelized OBJ, `resolution` voxels along its longest side
+    pub fn import_mesh(
+        &mut self,
+        path: &Path,
+        resolution: u32,
+        origin: glam::IVec3,
+    ) -> anyhow::Result<()> {
+        let model = voxelize::voxelize_obj(path, resolution)?;
+        self.place_models(&[model], origin, path);
+        Ok(())
+    }
+
+    // Place the models side by side along X from `origin`
+    fn place_models(&mut self, models: &[VoxModel], origin: glam::IVec3, source: &Path) {
+        let (min, max) = self.params.bounds();
+
+        let mut corner = origin;
+        let (mut placed, mut skipped) = (0, 0);
+        for model in models {
+            for (position, color) in &model.voxels {
+                let position = corner + position.as_ivec3();
+                // Only the chunk grid is rendered
+                if position.cmplt(min).any() || position.cmpge(max).any() {
+                    skipped += 1;
+                    continue;
                 }
+
+                self.set_voxel(
+                    position,
+                    Voxel {
+                        color: color | 0xFF000000,
+                    },
+                );
+                placed += 1;
             }
+            corner.x += model.size.x as i32 + 1;
         }
 
-        // Fill the octree
-        let mut octree = SVO::new();
-        let max_depth = (params.view_distance * CHUNK_SIZE).ilog2();
+        if skipped > 0 {
+            log::warn!(
+                "{skipped} voxels of {} are outside of the world",
+                source.display()
+            );
+        }
+        log::info!("Imported {placed} voxels from {}", source.display());
+        log::info!("{}", self.memory_stats());
+    }
 
-        for entry in chunks.iter() {
-            let chunk_coord = entry.key();
-            let chunk = entry.value();
+    // Air outside of the loaded chunks
+    pub fn get_voxel(&self, position: glam::IVec3) -> Voxel {
+        let (chunk_coord, local) = Self::split_position(position);
+        self.chunks
+            .get(&chunk_coord)
+            .map_or(Voxel::default(), |chunk| chunk.get(local))
+    }
 
-            for (index, voxel) in chunk.iter_voxels() {
-                let local_voxel_coord = Chunk::index_to_local_pos(index);
-                let global_voxel_coord = Chunk::local_to_world_pos(&local_voxel_coord, chunk_coord);
+    // The transparent voxel around `position` (under water), seen through by the raycasts
+    pub fn medium_at(&self, position: WorldPosition) -> Option<Voxel> {
+        Some(self.get_voxel(position.voxel())).filter(|voxel| !voxel.is_air() && !voxel.is_opaque())
+    }
 
-                octree.insert(global_voxel_coord, voxel.color, max_depth);
+    // Creates the chunk if needed, its structure is rebuilt by `rebuild_dirty_chunks`. Deferred until
+    // the chunk is generated for the terrain chunks not loaded
+    pub fn set_voxel(&mut self, position: glam::IVec3, voxel: Voxel) {
+        let (chunk_coord, local) = Self::split_position(position);
+        let loaded = self.chunks.contains_key(&chunk_coord);
+        if self.background.is_some() && self.params.is_terrain_chunk(chunk_coord) {
+            self.terrain_edits
+                .entry(chunk_coord)
+                .or_default()
+                .insert(local, voxel);
+            if !loaded {
+                return;
             }
         }
+        if voxel.is_air() && !loaded {
+            return;
+        }
+        self.chunks
+            .entry(chunk_coord)
+            .or_insert_with(Chunk::new)
+            .set(local, voxel);
+        self.dirty_chunks.insert(chunk_coord);
+    }
 
-        Self {
-            chunks,
-            octree,
-            params,
+    // Decoration voxel of another chunk: only placed into air, deferred like the edits
+    fn place_spilled_voxel(&mut self, position: glam::IVec3, voxel: Voxel) {
+        let (chunk_coord, local) = Self::split_position(position);
+        if self.params.is_terrain_chunk(chunk_coord) {
+            self.spilled_features
+                .entry(chunk_coord)
+                .or_default()
+                .insert(local, voxel);
+            if !self.chunks.contains_key(&chunk_coord) {
+                return;
+            }
+        }
+        // Spilled again when its chunk is generated again, a broken leaf doesn't grow back. Only
+        // tracked in the terrain chunks
+        let edited = self
+            .terrain_edits
+            .get(&chunk_coord)
+            .is_some_and(|edits| edits.contains_key(&local));
+        if !edited && place_into_air(&self.chunks, chunk_coord, local, voxel) {
+            self.dirty_chunks.insert(chunk_coord);
+        }
+    }
+
+    // Rebuild the structures of the edited chunks, in parallel. Return their coordinates to upload them
+    pub fn rebuild_dirty_chunks(&mut self) -> Vec<glam::IVec3> {
+        let dirty: Vec<_> = self.dirty_chunks.drain().collect();
+        dirty.par_iter().for_each(|chunk_coord| {
+            if let Some(mut chunk) = self.chunks.get_mut(chunk_coord) {
+                chunk.rebuild_structure(&self.params);
+            }
+        });
+        dirty
+    }
+
+    // Chunk coordinate (rounded down, also for negative positions) & position in the chunk
+    fn split_position(position: glam::IVec3) -> (glam::IVec3, glam::UVec3) {
+        let size = glam::IVec3::splat(CHUNK_SIZE as i32);
+        (
+            position.div_euclid(size),
+            position.rem_euclid(size).as_uvec3(),
+        )
+    }
+
+    // First voxel along the ray & the normal of the face it is entered through (zero if the ray
+    // starts inside it), by stepping from voxel to voxel. Looks through the transparent voxels like
+    // the one the ray starts in, so the water around an underwater camera isn't picked
+    pub fn raycast(
+        &self,
+        origin: WorldPosition,
+        direction: glam::Vec3,
+        max_distance: f32,
+    ) -> Option<(glam::IVec3, glam::IVec3)> {
+        let direction = direction.normalize();
+        let step = direction.signum().as_ivec3();
+        let t_delta = direction.recip().abs();
+
+        // Only the position in the first voxel is needed, so far origins stay precise
+        let mut voxel = origin.voxel();
+        let in_voxel = origin.offset - origin.offset.floor();
+        let next_boundary = step.max(glam::IVec3::ZERO).as_vec3();
+        let mut t_max = glam::Vec3::select(
+            direction.cmpeq(glam::Vec3::ZERO),
+            glam::Vec3::INFINITY,
+            (next_boundary - in_voxel) / direction,
+        );
+        let mut normal = glam::IVec3::ZERO;
+        let medium = self.medium_at(origin);
+
+        let mut t = 0.0;
+        while t <= max_distance {
+            let current = self.get_voxel(voxel);
+            if !current.is_air() && Some(current) != medium {
+                return Some((voxel, normal));
+            }
+            let axis = t_max.min_position();
+            t = t_max[axis];
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            normal = glam::IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+        None
+    }
+
+    // Save the voxels in [min, max) as a single model
+    pub fn export_vox(
+        &self,
+        path: &Path,
+        min: glam::IVec3,
+        max: glam::IVec3,
+    ) -> anyhow::Result<()> {
+        let size = (max - min).as_uvec3();
+        let mut voxels = Vec::new();
+
+        for entry in self.chunks.iter() {
+            for (index, voxel) in entry.value().iter_voxels() {
+                let local = Chunk::index_to_local_pos(index);
+                let position = Chunk::local_to_world_pos(&local, entry.key());
+                if position.cmpge(min).all() && position.cmplt(max).all() {
+                    voxels.push(((position - min).as_uvec3(), voxel.color));
+                }
+            }
+        }
+
+        let count = voxels.len();
+        vox::save(path, &VoxModel { size, voxels })?;
+        log::info!("Exported {count} voxels to {}", path.display());
+        Ok(())
+    }
+
+    // Acceleration structures of all the chunks, in parallel. Return their size in bytes
+    pub fn build_structures(chunks: &DashMap<glam::IVec3, Chunk>, params: &WorldParams) -> usize {
+        chunks
+            .par_iter_mut()
+            .for_each(|mut entry| entry.rebuild_structure(params));
+
+        let size = chunks
+            .iter()
+            .map(|entry| size_of_val(entry.gpu_words()))
+            .sum();
+        log::info!(
+            "Chunk structures built ({}): {} kB",
+            params.structure_name(),
+            size / 1024
+        );
+        size
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats::new(&self.chunks)
+    }
+
+    pub fn chunk_coords(&self) -> Vec<glam::IVec3> {
+        self.chunks.iter().map(|entry| *entry.key()).collect()
+    }
+
+    // Boxes (min corner, size) of the non empty nodes at `level`, as if the chunk structures were the
+    // bottom of a single world octree
+    pub fn level_boxes(
+        &self,
+        level: u32,
+        visit: impl Fn(glam::IVec3, i32) -> bool,
+    ) -> Vec<(glam::IVec3, i32)> {
+        let chunk_depth = CHUNK_SIZE.ilog2();
+        let chunk_level = self.params.max_depth() - chunk_depth;
+        let (grid_min, _) = self.params.bounds();
+
+        let mut boxes = Vec::new();
+        for entry in self.chunks.iter() {
+            if entry.gpu_words().is_empty() {
+                continue;
+            }
+            let origin = Chunk::local_to_world_pos(&glam::IVec3::ZERO, entry.key());
+
+            if level <= chunk_level {
+                // Box above the chunk, shared with its neighbours
+                let size = 2_i32.pow(self.params.max_depth() - level);
+                let min =
+                    grid_min + (origin - grid_min).div_euclid(glam::IVec3::splat(size)) * size;
+                if !boxes.contains(&(min, size)) && visit(min, size) {
+                    boxes.push((min, size));
+                }
+            } else {
+                let chunk_boxes =
+                    entry.level_boxes(level - chunk_level, |min, size| visit(origin + min, size));
+                boxes.extend(
+                    chunk_boxes
+                        .into_iter()
+                        .map(|(min, size)| (origin + min, size)),
+                );
+            }
+        }
+
+        boxes
+    }
+}
+
+impl MemoryStats {
+    pub fn new(chunks: &DashMap<glam::IVec3, Chunk>) -> Self {
+        let mut stats = Self {
+            chunks: chunks.len(),
+            uniform_chunks: 0,
+            bytes: 0,
+        };
+        for entry in chunks.iter() {
+            stats.uniform_chunks += entry.uniform().is_some() as usize;
+            stats.bytes += entry.memory_size();
         }
+        stats
+    }
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Against a full voxel per position
+        let raw_bytes = self.chunks * (CHUNK_SIZE as usize).pow(3) * size_of::<Voxel>();
+        write!(
+            f,
+            "Voxels: {} chunks ({} uniform) | {} kB, {} kB unpacked",
+            self.chunks,
+            self.uniform_chunks,
+            self.bytes / 1024,
+            raw_bytes / 1024
+        )
     }
 }
 
 impl WorldResource {
-    pub fn new(device: &wgpu::Device, view_distance: u32) -> Self {
+    pub fn new(device: &wgpu::Device, params: &WorldParams) -> Self {
         let uniform = WorldUniform {
-            view_distance: view_distance,
+            view_distance: params.view_distance,
+            brickmap: (params.chunk_structure == ChunkStructure::Brickmap) as u32,
+            grid_origin: params.origin.to_array(),
             ..Default::default()
         };
 
-        // Sparse Voxel Octree buffer
-        let svo_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Chunk Pool Storage Buffer"),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            size: 32000000 as u64, // TODO: Dynamic allocation
-            mapped_at_creation: false,
-        });
+        // Chunk structures & the grid of their roots
+        let chunk_pool = ChunkPool::new(device, params.view_distance, params.origin);
+        let voxel_objects = VoxelObjectPool::new(device);
 
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -92,10 +620,10 @@ impl WorldResource {
         let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("World Bind Group Layout"),
             entries: &[
-                // Binding 0 -> SVO
+                // Binding 0 -> Chunk SVOs
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Storage { read_only: true },
                         has_dynamic_offset: false,
@@ -106,7 +634,7 @@ impl WorldResource {
                 // Binding 1 -> World Uniform
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -114,6 +642,39 @@ impl WorldResource {
                     },
                     count: None,
                 },
+                // Binding 2 -> Voxel object SVOs
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Binding 3 -> Voxel object instances
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Binding 4 -> Chunk grid
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -123,30 +684,312 @@ impl WorldResource {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: svo_buffer.as_entire_binding(),
+                    resource: chunk_pool.node_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: uniform_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: voxel_objects.node_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: voxel_objects.instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: chunk_pool.grid_buffer.as_entire_binding(),
+                },
             ],
         });
 
         Self {
             layout,
             bind_group,
-            svo_buffer,
+            chunk_pool,
+            uniform,
             uniform_buffer,
+            voxel_objects,
+            grid_origin: params.origin,
+            render_origin: glam::IVec3::ZERO,
         }
     }
 
     pub fn upload(&mut self, queue: &wgpu::Queue, world: &World) {
-        queue.write_buffer(&self.svo_buffer, 0, world.octree.as_bytes());
+        for entry in world.chunks.iter() {
+            if world.params.contains_chunk(*entry.key()) {
+                self.upload_chunk(queue, *entry.key(), entry.value());
+            }
+        }
+    }
+
+    // Follow the chunk grid of the world, then re-upload the given chunks. The ones not in the world
+    // anymore are removed, the ones outside of the grid wait for it to reach them
+    pub fn update_chunks(
+        &mut self,
+        queue: &wgpu::Queue,
+        world: &World,
+        chunk_coords: &[glam::IVec3],
+    ) {
+        if world.params.origin != self.grid_origin {
+            self.grid_origin = world.params.origin;
+            self.chunk_pool.move_grid(queue, self.grid_origin);
+            self.set_render_origin(queue, self.render_origin);
+        }
+
+        for &chunk_coord in chunk_coords {
+            match world.chunks.get(&chunk_coord) {
+                Some(_) if !world.params.contains_chunk(chunk_coord) => (),
+                Some(chunk) => self.upload_chunk(queue, chunk_coord, &chunk),
+                None => self.chunk_pool.remove_chunk(queue, chunk_coord),
+            }
+        }
+    }
+
+    // The chunk is skipped if it doesn't fit in the pool or the grid
+    pub fn upload_chunk(&mut self, queue: &wgpu::Queue, chunk_coord: glam::IVec3, chunk: &Chunk) {
+        if let Err(e) = self
+            .chunk_pool
+            .upload_chunk(queue, chunk_coord, chunk.gpu_words())
+        {
+            log::warn!("{e:#}");
+        }
+    }
+
+    pub fn add_voxel_model(
+        &mut self,
+        queue: &wgpu::Queue,
+        model: &VoxelModel,
+    ) -> anyhow::Result<VoxelModelId> {
+        self.voxel_objects.add_model(queue, model)
+    }
+
+    pub fn update_voxel_objects(
+        &mut self,
+        queue: &wgpu::Queue,
+        objects: &[VoxelObject],
+        render_origin: glam::IVec3,
+    ) {
+        self.uniform.object_count = self.voxel_objects.upload(queue, objects, render_origin);
+        self.write_uniform(queue);
+    }
+
+    // Chunk the world is rendered relative to, the one of the camera
+    pub fn set_render_origin(&mut self, queue: &wgpu::Queue, render_origin: glam::IVec3) {
+        self.render_origin = render_origin;
+        self.uniform.grid_origin = (self.grid_origin - render_origin).to_array();
+        self.write_uniform(queue);
+    }
+
+    pub fn set_heatmap(&mut self, queue: &wgpu::Queue, heatmap: bool) {
+        self.uniform.heatmap = heatmap as u32;
+        self.write_uniform(queue);
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+}
+
+impl WorldParams {
+    // Depth of the octree leaves, the voxels
+    pub fn max_depth(&self) -> u32 {
+        (self.view_distance * CHUNK_SIZE).ilog2()
+    }
+
+    // Grid centered on the origin horizontally, from y = 0 up
+    pub fn centered_origin(view_distance: u32) -> glam::IVec3 {
+        let half = view_distance as i32 / 2;
+        glam::ivec3(-half, 0, -half)
+    }
+
+    // (min, max) voxel coordinates of the chunk grid, max excluded
+    pub fn bounds(&self) -> (glam::IVec3, glam::IVec3) {
+        let min = self.origin * CHUNK_SIZE as i32;
+        (min, min + (self.view_distance * CHUNK_SIZE) as i32)
+    }
+
+    // Terrain chunks of the grid, the others start as air
+    pub fn terrain_chunks(&self) -> impl Iterator<Item = glam::IVec3> + '_ {
+        let size = self.view_distance as i32;
+        (0..size)
+            .flat_map(move |x| {
+                (0..size).map(move |z| glam::ivec3(self.origin.x + x, 0, self.origin.z + z))
+            })
+            .filter(|chunk_coord| self.contains_chunk(*chunk_coord))
+    }
+
+    // Filled by the terrain generation, the chunks at y = 0 (the terrain goes up from there). Only
+    // loaded inside of the grid
+    pub fn is_terrain_chunk(&self, chunk_coord: glam::IVec3) -> bool {
+        chunk_coord.y == 0
+    }
+
+    pub fn contains_chunk(&self, chunk_coord: glam::IVec3) -> bool {
+        let offset = chunk_coord - self.origin;
+        offset.min_element() >= 0 && offset.max_element() < self.view_distance as i32
+    }
+
+    // Above the terrain, in the middle of the grid
+    pub fn spawn_position(&self) -> WorldPosition {
+        let half = self.view_distance as i32 / 2;
+        let chunk = glam::ivec3(self.origin.x + half, 2, self.origin.z + half);
+        WorldPosition::new(chunk, glam::Vec3::ZERO)
+    }
+
+    // The same world with each chunk structure & octree layout, for the benchmarks
+    pub fn structure_variants(&self) -> Vec<WorldParams> {
+        let octrees = SvoLayout::ALL.map(|svo_layout| WorldParams {
+            chunk_structure: ChunkStructure::Octree,
+            svo_layout,
+            ..self.clone()
+        });
+        let brickmap = WorldParams {
+            chunk_structure: ChunkStructure::Brickmap,
+            ..self.clone()
+        };
+        octrees.into_iter().chain([brickmap]).collect()
+    }
+
+    pub fn structure_name(&self) -> String {
+        match self.chunk_structure {
+            ChunkStructure::Octree => format!("octree {:?}", self.svo_layout),
+            ChunkStructure::Brickmap => "brickmap".to_string(),
+        }
     }
 }
 
 impl Default for WorldParams {
     fn default() -> WorldParams {
-        WorldParams { view_distance: 16 }
+        WorldParams {
+            view_distance: 16,
+            origin: Self::centered_origin(16),
+            chunk_structure: ChunkStructure::Octree,
+            svo_layout: SvoLayout::Insertion,
+            terrain: TerrainParams::default(),
+        }
+    }
+}
+
+// Return whether the voxel was placed. Creates the chunk if needed
+fn place_into_air(
+    chunks: &DashMap<glam::IVec3, Chunk>,
+    chunk_coord: glam::IVec3,
+    local: glam::UVec3,
+    voxel: Voxel,
+) -> bool {
+    let mut chunk = chunks.entry(chunk_coord).or_insert_with(Chunk::new);
+    if !chunk.get(local).is_air() {
+        return false;
+    }
+    chunk.set(local, voxel);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_around(world: &mut World, focus: glam::IVec3) -> Vec<glam::IVec3> {
+        let changed = world.update_generation(focus);
+        while world.generation_progress().is_some() {
+            if world.integrate_generated_chunk().is_none() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+        changed
+    }
+
+    #[test]
+    fn unloaded_chunks_get_their_edits_back() {
+        let params = WorldParams {
+            view_distance: 4,
+            origin: WorldParams::centered_origin(4),
+            ..Default::default()
+        };
+        let mut world = World::new_in_background(params);
+        let corner = glam::ivec3(-2, 0, -2);
+        generate_around(&mut world, corner);
+        assert!(world.chunk_coords().contains(&corner));
+
+        let position = corner * CHUNK_SIZE as i32 + glam::ivec3(3, 5, 7);
+        let voxel = Voxel::new(glam::vec3(1.0, 0.0, 1.0), true);
+        world.set_voxel(position, voxel);
+        world.rebuild_dirty_chunks();
+
+        // The grid around it doesn't reach the corner
+        let changed = generate_around(&mut world, glam::ivec3(8, 0, 8));
+        assert!(changed.contains(&corner));
+        assert!(!world.chunk_coords().contains(&corner));
+
+        generate_around(&mut world, corner);
+        assert_eq!(world.get_voxel(position), voxel);
+    }
+
+    #[test]
+    fn the_grid_follows_the_focus() {
+        let params = WorldParams {
+            view_distance: 4,
+            origin: WorldParams::centered_origin(4),
+            ..Default::default()
+        };
+        let mut world = World::new_in_background(params);
+        generate_around(&mut world, glam::IVec3::ZERO);
+
+        // Above the terrain
+        let above = glam::ivec3(1, 1, 1);
+        let voxel = Voxel::new(glam::vec3(1.0, 0.0, 1.0), true);
+        world.set_voxel(above * CHUNK_SIZE as i32, voxel);
+        world.rebuild_dirty_chunks();
+
+        let far = glam::ivec3(20, 0, -20);
+        let changed = generate_around(&mut world, far);
+        assert_eq!(world.params.origin, glam::ivec3(18, 0, -22));
+        assert!(world.chunk_coords().contains(&far));
+        // Kept outside of the grid, not on the GPU
+        assert!(world.chunk_coords().contains(&above));
+        assert!(!changed.contains(&above));
+
+        // Uploaded again once the grid reaches it
+        let changed = generate_around(&mut world, glam::IVec3::ZERO);
+        assert!(changed.contains(&above));
+        assert_eq!(world.get_voxel(above * CHUNK_SIZE as i32), voxel);
+    }
+
+    #[test]
+    fn set_voxel_at_a_negative_position() {
+        let mut world = World::new(WorldParams {
+            view_distance: 2,
+            ..Default::default()
+        });
+        let position = glam::ivec3(-1, -17, -16);
+        assert_eq!(
+            World::split_position(position),
+            (glam::ivec3(-1, -2, -1), glam::uvec3(15, 15, 0))
+        );
+
+        let voxel = Voxel::new(glam::vec3(1.0, 0.0, 1.0), true);
+        world.set_voxel(position, voxel);
+        assert_eq!(world.get_voxel(position), voxel);
+        assert_eq!(
+            world
+                .chunks
+                .get(&glam::ivec3(-1, -2, -1))
+                .unwrap()
+                .get(glam::uvec3(15, 15, 0)),
+            voxel
+        );
+        // Its neighbours across the chunk borders are untouched
+        assert!(world.get_voxel(position + glam::IVec3::X).is_air());
+        assert!(world.get_voxel(position - glam::IVec3::Z).is_air());
+
+        assert_eq!(world.rebuild_dirty_chunks(), vec![glam::ivec3(-1, -2, -1)]);
+        assert!(world.rebuild_dirty_chunks().is_empty());
     }
 }
@@ -0,0 +1,129 @@
+use std::ops::{Add, AddAssign};
+
+use crate::chunk::CHUNK_SIZE;
+
+// Position that keeps its f32 precision far from the origin: the chunk it is in & the offset in
+// that chunk, always in [0, CHUNK_SIZE)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WorldPosition {
+    pub chunk: glam::IVec3,
+    pub offset: glam::Vec3,
+}
+
+impl WorldPosition {
+    pub fn new(chunk: glam::IVec3, offset: glam::Vec3) -> Self {
+        let size = CHUNK_SIZE as f32;
+        let carry = (offset / size).floor();
+        Self {
+            chunk: chunk + carry.as_ivec3(),
+            // Can round up to the chunk size itself for tiny negative offsets
+            offset: (offset - carry * size).min(glam::Vec3::splat(size.next_down())),
+        }
+    }
+
+    pub fn from_dvec3(position: glam::DVec3) -> Self {
+        let size = CHUNK_SIZE as f64;
+        let chunk = (position / size).floor();
+        Self::new(chunk.as_ivec3(), (position - chunk * size).as_vec3())
+    }
+
+    // Min corner of the voxel
+    pub fn from_voxel(voxel: glam::IVec3) -> Self {
+        let size = glam::IVec3::splat(CHUNK_SIZE as i32);
+        Self {
+            chunk: voxel.div_euclid(size),
+            offset: voxel.rem_euclid(size).as_vec3(),
+        }
+    }
+
+    pub fn as_dvec3(&self) -> glam::DVec3 {
+        (self.chunk * CHUNK_SIZE as i32).as_dvec3() + self.offset.as_dvec3()
+    }
+
+    // Voxel containing the position
+    pub fn voxel(&self) -> glam::IVec3 {
+        self.chunk * CHUNK_SIZE as i32 + self.offset.floor().as_ivec3()
+    }
+
+    // Position in a space whose origin is the min corner of `origin_chunk`, precise near that chunk
+    pub fn relative_to(&self, origin_chunk: glam::IVec3) -> glam::Vec3 {
+        ((self.chunk - origin_chunk) * CHUNK_SIZE as i32).as_vec3() + self.offset
+    }
+}
+
+impl Add<glam::Vec3> for WorldPosition {
+    type Output = Self;
+
+    fn add(self, delta: glam::Vec3) -> Self {
+        Self::new(self.chunk, self.offset + delta)
+    }
+}
+
+impl AddAssign<glam::Vec3> for WorldPosition {
+    fn add_assign(&mut self, delta: glam::Vec3) {
+        *self = *self + delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAR: f64 = 1e6; // A multiple of the chunk size, f32 only has a 1/16 step there
+
+    #[test]
+    fn new_carries_the_offset_into_the_chunk() {
+        let position = WorldPosition::new(glam::IVec3::ZERO, glam::vec3(-0.5, 16.0, 33.0));
+        assert_eq!(position.chunk, glam::ivec3(-1, 1, 2));
+        assert_eq!(position.offset, glam::vec3(15.5, 0.0, 1.0));
+
+        let chunk = glam::IVec3::splat((FAR / CHUNK_SIZE as f64) as i32);
+        let position = WorldPosition::new(chunk, glam::Vec3::splat(-1e-9));
+        assert_eq!(position.chunk, chunk - 1);
+        assert!(
+            position
+                .offset
+                .cmplt(glam::Vec3::splat(CHUNK_SIZE as f32))
+                .all()
+        );
+    }
+
+    #[test]
+    fn from_dvec3_is_exact_far_from_the_origin() {
+        let point = glam::dvec3(FAR + 0.25, -FAR - 0.25, FAR + 15.5);
+        let position = WorldPosition::from_dvec3(point);
+        assert_eq!(position.chunk, glam::ivec3(62500, -62501, 62500));
+        assert_eq!(position.offset, glam::vec3(0.25, 15.75, 15.5));
+        assert_eq!(position.as_dvec3(), point);
+    }
+
+    #[test]
+    fn relative_to_keeps_small_distances() {
+        for far in [FAR, -FAR] {
+            let a = WorldPosition::from_dvec3(glam::DVec3::splat(far + 0.5));
+            let b = a + glam::Vec3::splat(0.01);
+            let delta = b.relative_to(a.chunk) - a.relative_to(a.chunk);
+            assert!((delta - glam::Vec3::splat(0.01)).abs().max_element() < 1e-5);
+
+            // From a neighbour chunk
+            let origin = a.chunk + glam::ivec3(1, -2, 3);
+            let expected = (a.as_dvec3() - (origin * CHUNK_SIZE as i32).as_dvec3()).as_vec3();
+            assert_eq!(a.relative_to(origin), expected);
+        }
+    }
+
+    #[test]
+    fn voxel_rounds_down() {
+        let position = WorldPosition::from_dvec3(glam::dvec3(FAR + 0.5, -FAR - 0.5, -0.5));
+        assert_eq!(position.voxel(), glam::ivec3(1_000_000, -1_000_001, -1));
+
+        for voxel in [
+            glam::ivec3(1_000_000, -1_000_001, 7),
+            glam::IVec3::splat(-1),
+        ] {
+            let position = WorldPosition::from_voxel(voxel);
+            assert_eq!(position.voxel(), voxel);
+            assert_eq!(position.as_dvec3(), voxel.as_dvec3());
+        }
+    }
+}